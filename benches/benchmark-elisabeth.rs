@@ -1,6 +1,6 @@
 use concrete_core::math::random::RandomGenerator;
 use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
-use elisabeth::{u4, Encrypter, SystemParameters, LWE};
+use elisabeth::{u4, Encrypter, Nonce, SystemParameters, Transcipherer};
 use pprof::criterion::{Output, PProfProfiler};
 
 fn bench_encryption(c: &mut Criterion) {
@@ -10,7 +10,8 @@ fn bench_encryption(c: &mut Criterion) {
         "Elisabeth 60 - Encryption"
     };
 
-    let (mut enc, _dec) = Encrypter::<u4>::new::<u4>(&SystemParameters::n60, None, None, None);
+    let (enc, _dec) =
+        Encrypter::<u4>::new::<u4>(&SystemParameters::n60, (&(), &()), ((), ())).unwrap();
 
     let mut generator = RandomGenerator::new(None);
     let message = vec![u4(generator.random_uniform_n_lsb(4))];
@@ -19,8 +20,40 @@ fn bench_encryption(c: &mut Criterion) {
 
     c.bench_function(id, move |b| {
         b.iter_batched(
-            || message.clone(),
-            |message1216| enc.encrypt(black_box(&mut ciphertext), black_box(&message1216)),
+            || (Nonce::random(), message.clone()),
+            |(nonce, message1216)| {
+                enc.encrypt(nonce, black_box(&mut ciphertext), black_box(&message1216))
+                    .unwrap()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_encryption_throughput(c: &mut Criterion) {
+    let id = if cfg!(not(feature = "multithread")) {
+        "Elisabeth 60 - Encryption of 4096 nibbles - Monothreaded"
+    } else {
+        "Elisabeth 60 - Encryption of 4096 nibbles"
+    };
+
+    let (enc, _dec) =
+        Encrypter::<u4>::new::<u4>(&SystemParameters::n60, (&(), &()), ((), ())).unwrap();
+
+    let mut generator = RandomGenerator::new(None);
+    let message = (0..4096)
+        .map(|_| u4(generator.random_uniform_n_lsb(4)))
+        .collect::<Vec<_>>();
+
+    let mut ciphertext = vec![u4(0); message.len()];
+
+    c.bench_function(id, move |b| {
+        b.iter_batched(
+            Nonce::random,
+            |nonce| {
+                enc.encrypt(nonce, black_box(&mut ciphertext), black_box(&message))
+                    .unwrap()
+            },
             BatchSize::SmallInput,
         )
     });
@@ -38,31 +71,31 @@ fn bench_transcryption(c: &mut Criterion) {
     }
 
     #[cfg(not(feature = "single_key"))]
-    let ((sk, std_dev_lwe), _sk_out, pk) = SystemParameters::n60.generate_fhe_keys();
+    let (lwe_key, _sk_out, pk) = SystemParameters::n60.generate_fhe_keys().unwrap();
     #[cfg(feature = "single_key")]
-    let ((sk, std_dev_lwe), pk) = SystemParameters::n60.generate_fhe_keys();
+    let (lwe_key, pk) = SystemParameters::n60.generate_fhe_keys().unwrap();
 
-    let (mut encrypter, mut decrypter) = Encrypter::<u4>::new::<LWE>(
+    let encrypter = Encrypter::<u4>::from_symmetric_key(
         &SystemParameters::n60,
-        Some(&sk),
-        Some(std_dev_lwe.0),
-        Some(pk),
-    );
+        SystemParameters::n60.generate_symmetric_key(),
+    )
+    .unwrap();
+    let encrypted_key = encrypter.export_encrypted_key(&lwe_key).unwrap();
+    let transcipherer = Transcipherer::new(&SystemParameters::n60, encrypted_key, pk).unwrap();
 
     // message
     let mut generator = RandomGenerator::new(None);
     let message = vec![u4(generator.random_uniform_n_lsb::<u8>(4))];
 
     let mut ciphertext = vec![u4(0)];
-    let mut transciphered = vec![LWE::allocate(sk.key_size().to_lwe_size())];
 
-    encrypter.encrypt(&mut ciphertext, &message);
+    let nonce = Nonce::random();
+    encrypter.encrypt(nonce, &mut ciphertext, &message).unwrap();
 
-    decrypter.decrypt(&mut transciphered, &ciphertext);
     c.bench_function(id.as_str(), move |b| {
         b.iter_batched(
             || ciphertext.clone(),
-            |ctx| decrypter.decrypt(black_box(&mut transciphered), black_box(&ctx)),
+            |ctx| transcipherer.transcipher(nonce, black_box(&ctx)).unwrap(),
             BatchSize::SmallInput,
         )
     });
@@ -71,6 +104,6 @@ fn bench_transcryption(c: &mut Criterion) {
 criterion_group! {
     name = benches;
     config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
-    targets = bench_encryption, bench_transcryption
+    targets = bench_encryption, bench_encryption_throughput, bench_transcryption
 }
 criterion_main!(benches);
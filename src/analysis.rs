@@ -0,0 +1,212 @@
+//! Cryptographic properties of the S-boxes and of the blocks of a filter.
+//!
+//! A function is given by its table over `k` input nibbles, the nibble `j` of the input being
+//! bits `4j..4j + 4` of the index. The bitwise properties (differential uniformity, linearity and
+//! algebraic degree) see nibbles as vectors of 4 bits, as usual. Since Elisabeth combines nibbles
+//! with additions modulo 16, the differential uniformity with respect to that addition is given as
+//! well.
+//!
+//! For a function of more than one nibble, only the input differences on a single nibble are
+//! considered, so that the analysis of a block of Elisabeth-4 stays within a few seconds.
+//! For an S-box, these are all the differences. For a block, the last nibble is added to the output
+//! as is, so that its differences always hold: the differential uniformity is then given for each
+//! input nibble as well.
+
+use crate::{error::ElisabethError, filter::Filter, u4, FilterSpec};
+
+/// The widest block that can be analyzed, for which the Walsh transform takes 128 MiB.
+const MAX_BLOCK_WIDTH: usize = 6;
+
+/// The properties shared by S-boxes and blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Properties {
+    /// The highest number of inputs `x` such that `f(x ^ a) ^ f(x) = b`, for `a != 0`.
+    pub differential_uniformity: usize,
+    /// The highest number of inputs `x` such that `f(x + a) - f(x) = b` modulo 16, for `a != 0`.
+    pub additive_differential_uniformity: usize,
+    /// The highest absolute value of the Walsh transform of a non-zero component of `f`.
+    pub linearity: usize,
+    /// The highest algebraic degree of the coordinates of `f`.
+    pub algebraic_degree: u32,
+    /// Whether every output is reached by the same number of inputs.
+    pub balanced: bool,
+}
+
+/// The properties of an S-box.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SboxReport {
+    pub properties: Properties,
+    /// The number of `x` such that `S(x) = x`.
+    pub fixed_points: usize,
+    /// Whether the S-box is a permutation.
+    pub bijective: bool,
+    /// Whether `S(x + 8) = -S(x)` for every `x`, which `LWE::apply_sbox` needs.
+    pub negacyclic: bool,
+}
+
+/// Computes the properties of an S-box.
+#[must_use]
+pub fn analyze_sbox(sbox: &[u4; 16]) -> SboxReport {
+    let table = sbox.iter().map(|u| u.0 & 0xF).collect::<Vec<_>>();
+    let properties = properties(
+        &table,
+        &differential_uniformity(&table, 1, false),
+        &differential_uniformity(&table, 1, true),
+    );
+    SboxReport {
+        properties,
+        fixed_points: (0..16).filter(|&x| table[x] as usize == x).count(),
+        bijective: properties.balanced,
+        negacyclic: crate::filter::is_negacyclic(sbox),
+    }
+}
+
+/// The properties of a block of a filter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockReport {
+    pub properties: Properties,
+    /// The differential uniformity restricted to differences on each input nibble.
+    pub differential_uniformity_by_nibble: Vec<usize>,
+    /// The additive differential uniformity restricted to differences on each input nibble.
+    pub additive_differential_uniformity_by_nibble: Vec<usize>,
+}
+
+/// Computes the properties of the function computed by a block of the given filter, from its
+/// `block_width` key nibbles to its output nibble.
+///
+/// # Errors
+///
+/// Returns `InvalidFilter` if the filter fails `FilterSpec::validate`, or if its blocks are wider
+/// than 6 nibbles, whose function would be too large to tabulate.
+pub fn analyze_filter_block(spec: &FilterSpec) -> Result<BlockReport, ElisabethError> {
+    let width = spec.block_width;
+    if width > MAX_BLOCK_WIDTH {
+        return Err(ElisabethError::InvalidFilter(format!(
+            "blocks of {width} nibbles are too wide to be analyzed, the limit is {MAX_BLOCK_WIDTH}"
+        )));
+    }
+    let filter = Filter::new(spec.clone())?;
+    let sboxes = filter.sboxes(&())?;
+
+    let mut block = vec![u4(0); width];
+    let table = (0..1_usize << (4 * width))
+        .map(|x| {
+            for (j, nibble) in block.iter_mut().enumerate() {
+                *nibble = u4(((x >> (4 * j)) & 0xF) as u8);
+            }
+            filter.filter_block(&block, &sboxes, &()).map(|u| u.0)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let differential_uniformity_by_nibble = differential_uniformity(&table, width, false);
+    let additive_differential_uniformity_by_nibble = differential_uniformity(&table, width, true);
+    Ok(BlockReport {
+        properties: properties(
+            &table,
+            &differential_uniformity_by_nibble,
+            &additive_differential_uniformity_by_nibble,
+        ),
+        differential_uniformity_by_nibble,
+        additive_differential_uniformity_by_nibble,
+    })
+}
+
+fn properties(table: &[u8], differential: &[usize], additive_differential: &[usize]) -> Properties {
+    let mut counts = [0_usize; 16];
+    for &y in table {
+        counts[y as usize] += 1;
+    }
+
+    Properties {
+        differential_uniformity: differential.iter().copied().max().unwrap_or(0),
+        additive_differential_uniformity: additive_differential.iter().copied().max().unwrap_or(0),
+        linearity: linearity(table),
+        algebraic_degree: algebraic_degree(table),
+        balanced: counts.iter().all(|&c| c == counts[0]),
+    }
+}
+
+/// Returns, for each input nibble, the highest number of inputs mapped to the same output
+/// difference by a difference on that nibble. Differences are taken modulo 16 if `additive` is
+/// set, and bitwise otherwise.
+fn differential_uniformity(table: &[u8], nibbles: usize, additive: bool) -> Vec<usize> {
+    (0..nibbles)
+        .map(|j| {
+            let shift = 4 * j;
+            let mut max = 0;
+            for a in 1..16 {
+                let mut counts = [0_usize; 16];
+                for (x, &y) in table.iter().enumerate() {
+                    let difference = if additive {
+                        let shifted = (x & !(0xF << shift)) | ((((x >> shift) + a) & 0xF) << shift);
+                        table[shifted].wrapping_sub(y) & 0xF
+                    } else {
+                        table[x ^ (a << shift)] ^ y
+                    };
+                    counts[difference as usize] += 1;
+                }
+                max = max.max(counts.iter().copied().max().unwrap_or(0));
+            }
+            max
+        })
+        .collect()
+}
+
+fn linearity(table: &[u8]) -> usize {
+    let mut max = 0;
+    let mut spectrum = vec![0_i64; table.len()];
+    for b in 1..16_u8 {
+        for (w, &y) in spectrum.iter_mut().zip(table) {
+            *w = if (b & y).count_ones() % 2 == 0 { 1 } else { -1 };
+        }
+        walsh_hadamard(&mut spectrum);
+        let component = spectrum.iter().map(|w| w.unsigned_abs()).max().unwrap_or(0);
+        max = max.max(component as usize);
+    }
+    max
+}
+
+/// The fast Walsh-Hadamard transform, in place.
+fn walsh_hadamard(values: &mut [i64]) {
+    let mut half = 1;
+    while half < values.len() {
+        for chunk in values.chunks_mut(2 * half) {
+            let (low, high) = chunk.split_at_mut(half);
+            for (l, h) in low.iter_mut().zip(high.iter_mut()) {
+                let (a, b) = (*l, *h);
+                *l = a + b;
+                *h = a - b;
+            }
+        }
+        half *= 2;
+    }
+}
+
+/// Returns the highest degree of the algebraic normal forms of the output bits, computed with the
+/// Möbius transform.
+fn algebraic_degree(table: &[u8]) -> u32 {
+    let mut degree = 0;
+    let mut anf = vec![false; table.len()];
+    for bit in 0..4 {
+        for (a, &y) in anf.iter_mut().zip(table) {
+            *a = (y >> bit) & 1 == 1;
+        }
+        let mut half = 1;
+        while half < anf.len() {
+            for chunk in anf.chunks_mut(2 * half) {
+                let (low, high) = chunk.split_at_mut(half);
+                for (l, h) in low.iter().zip(high.iter_mut()) {
+                    *h ^= *l;
+                }
+            }
+            half *= 2;
+        }
+        degree = anf
+            .iter()
+            .enumerate()
+            .filter(|(_, &a)| a)
+            .map(|(monomial, _)| monomial.count_ones())
+            .fold(degree, u32::max);
+    }
+    degree
+}
@@ -0,0 +1,442 @@
+use super::{BackendKey, FheBackend};
+use crate::{
+    error::ElisabethError, nibble::Uint, secret::FheSecretKey, storage, SystemParameters, Torus,
+};
+use concrete_commons::{Numeric, StandardDev};
+use concrete_core::{
+    crypto::{
+        bootstrap::BootstrapKey,
+        cross::{blind_rotate, constant_sample_extract},
+        encoding::{Cleartext, Encoder, Plaintext, RealEncoder},
+        glwe::GlweCiphertext,
+        lwe::{LweCiphertext, LweKeyswitchKey},
+        secret::{GlweSecretKey, LweSecretKey},
+        LweSize,
+    },
+    math::{
+        decomposition::{DecompositionBaseLog, DecompositionLevelCount, SignedDecomposable},
+        fft::{AlignedVec, Complex64, Fft, FourierPolynomial},
+        random::{EncryptionRandomGenerator, RandomGenerator},
+        tensor::{AsMutSlice, AsMutTensor, AsRefSlice, AsRefTensor},
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, path::Path};
+use zeroize::Zeroize;
+
+/// The backend built on concrete-core 0.1.9.
+pub struct Concrete;
+
+/// A struct that encapsulates both the bootstrapping and keyswitching key of `Concrete`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PublicKey {
+    pub bsk: BootstrapKey<Vec<Complex64>>,
+    pub ksk: LweKeyswitchKey<Vec<Torus>>,
+    #[cfg(not(feature = "single_key"))]
+    pub ksk_inv: LweKeyswitchKey<Vec<Torus>>,
+}
+
+impl BackendKey for PublicKey {
+    type Backend = Concrete;
+}
+
+impl BackendKey for (FheSecretKey, StandardDev) {
+    type Backend = Concrete;
+}
+
+/// The widest nibbles this backend evaluates. Its FHE parameters, those of `n60`, were chosen for
+/// nibbles of 4 bits: the rounding of a bootstrapping alone adds a noise of standard deviation
+/// about `2^-7.5`, while nibbles of 5 bits only tolerate `2^-6`, so that about one bootstrapping
+/// in a hundred would fail.
+const MAX_BITS: usize = 4;
+
+/// Checks that this backend can evaluate nibbles of `BITS` bits without errors.
+fn check_width<const BITS: usize>() -> Result<(), ElisabethError> {
+    if BITS <= MAX_BITS {
+        Ok(())
+    } else {
+        Err(ElisabethError::ParameterMismatch(format!(
+            "the FHE parameters only support nibbles of up to {MAX_BITS} bits, not {BITS}"
+        )))
+    }
+}
+
+/// Encodes a nibble in the most significant bits of the torus.
+fn encode<const BITS: usize>(u: Uint<BITS>) -> Torus {
+    Torus::from(u.0) << (<Torus as Numeric>::BITS - BITS)
+}
+
+/// The buffers of a bootstrapping, kept from one bootstrapping to the next by each thread.
+struct Scratch {
+    fft: Fft,
+    dec_i_fft: FourierPolynomial<AlignedVec<Complex64>>,
+    tmp_dec_i_fft: FourierPolynomial<AlignedVec<Complex64>>,
+    res_fft: Vec<FourierPolynomial<AlignedVec<Complex64>>>,
+    accumulator: GlweCiphertext<Vec<Torus>>,
+}
+
+impl Scratch {
+    fn new(bsk: &BootstrapKey<Vec<Complex64>>) -> Self {
+        let polynomial_size = bsk.polynomial_size();
+        let fourier = || FourierPolynomial::allocate(Complex64::new(0., 0.), polynomial_size);
+        Self {
+            fft: Fft::new(polynomial_size),
+            dec_i_fft: fourier(),
+            tmp_dec_i_fft: fourier(),
+            res_fft: (0..bsk.glwe_size().0).map(|_| fourier()).collect(),
+            accumulator: GlweCiphertext::allocate(0, polynomial_size, bsk.glwe_size()),
+        }
+    }
+
+    fn fits(&self, bsk: &BootstrapKey<Vec<Complex64>>) -> bool {
+        (self.accumulator.polynomial_size(), self.accumulator.size())
+            == (bsk.polynomial_size(), bsk.glwe_size())
+    }
+}
+
+thread_local! {
+    static SCRATCH: RefCell<Option<Scratch>> = const { RefCell::new(None) };
+}
+
+fn check_lwe_size(found: LweSize, expected: LweSize, key: &str) -> Result<(), ElisabethError> {
+    if found == expected {
+        Ok(())
+    } else {
+        Err(ElisabethError::ParameterMismatch(format!(
+            "an LWE ciphertext of size {} cannot be used with the {} of input size {}",
+            found.0, key, expected.0
+        )))
+    }
+}
+
+impl FheBackend for Concrete {
+    type Ciphertext = LweCiphertext<Vec<Torus>>;
+    type SecretKey = FheSecretKey;
+    /// The secret key and the standard deviation of the noise, as output by
+    /// `SystemParameters::generate_fhe_keys`.
+    type EncryptionKey = (FheSecretKey, StandardDev);
+    type PublicKey = PublicKey;
+    /// The accumulator of the bootstrapping.
+    type Lut = GlweCiphertext<Vec<Torus>>;
+    #[cfg(not(feature = "single_key"))]
+    type Keys = ((FheSecretKey, StandardDev), FheSecretKey, PublicKey);
+    #[cfg(feature = "single_key")]
+    type Keys = ((FheSecretKey, StandardDev), PublicKey);
+
+    fn generate_keys(params: &SystemParameters) -> Result<Self::Keys, ElisabethError> {
+        //parameters
+        #[cfg(not(feature = "single_key"))]
+        let (
+            (lwe_dimension, std_dev_lwe),
+            (glwe_dimension, polynomial_size, std_dev_glwe),
+            (base_log_bs, level_bs),
+            (base_log_ks, level_ks),
+            (base_log_ks_inv, level_ks_inv),
+        ) = params.fhe_parameters();
+        #[cfg(feature = "single_key")]
+        let (
+            (lwe_dimension, std_dev_lwe),
+            (glwe_dimension, polynomial_size, std_dev_glwe),
+            (base_log_bs, level_bs),
+            (base_log_ks, level_ks),
+        ) = params.fhe_parameters();
+
+        // secret keys
+        let mut generator = RandomGenerator::new(None);
+        let mut secret_generator = EncryptionRandomGenerator::new(None);
+        let sk_rlwe = GlweSecretKey::generate(glwe_dimension, polynomial_size, &mut generator);
+        let sk = FheSecretKey::new(LweSecretKey::generate(lwe_dimension, &mut generator));
+
+        // bootstrapping key
+        let mut coef_bsk = BootstrapKey::allocate(
+            <Torus as Numeric>::ZERO,
+            glwe_dimension.to_glwe_size(),
+            polynomial_size,
+            level_bs,
+            base_log_bs,
+            lwe_dimension,
+        );
+        coef_bsk.fill_with_new_key(&sk, &sk_rlwe, std_dev_glwe, &mut secret_generator);
+        let mut bsk = BootstrapKey::allocate(
+            Complex64::new(0., 0.),
+            glwe_dimension.to_glwe_size(),
+            polynomial_size,
+            level_bs,
+            base_log_bs,
+            lwe_dimension,
+        );
+        bsk.fill_with_forward_fourier(&coef_bsk);
+
+        let sk_out = FheSecretKey::new(sk_rlwe.into_lwe_secret_key());
+        let mut ksk = LweKeyswitchKey::allocate(
+            <Torus as Numeric>::ZERO,
+            level_ks,
+            base_log_ks,
+            sk_out.key_size(),
+            sk.key_size(),
+        );
+        ksk.fill_with_keyswitch_key(&sk_out, &sk, std_dev_lwe, &mut secret_generator);
+
+        #[cfg(not(feature = "single_key"))]
+        {
+            let mut ksk_inv = LweKeyswitchKey::allocate(
+                <Torus as Numeric>::ZERO,
+                level_ks_inv,
+                base_log_ks_inv,
+                sk.key_size(),
+                sk_out.key_size(),
+            );
+            ksk_inv.fill_with_keyswitch_key(&sk, &sk_out, std_dev_glwe, &mut secret_generator);
+
+            Ok(((sk, std_dev_lwe), sk_out, PublicKey { bsk, ksk, ksk_inv }))
+        }
+        #[cfg(feature = "single_key")]
+        Ok(((sk, std_dev_lwe), PublicKey { bsk, ksk }))
+    }
+
+    #[cfg(not(feature = "single_key"))]
+    fn read_keys(directory: &Path) -> Result<Self::Keys, ElisabethError> {
+        Ok((
+            (
+                storage::read_key(directory.join("secret/secret_key"))?,
+                storage::read_key(directory.join("secret/standard_deviation"))?,
+            ),
+            storage::read_key(directory.join("secret/secret_key_out"))?,
+            storage::read_key(directory.join("public/public_key"))?,
+        ))
+    }
+
+    #[cfg(feature = "single_key")]
+    fn read_keys(directory: &Path) -> Result<Self::Keys, ElisabethError> {
+        Ok((
+            (
+                storage::read_key(directory.join("secret/secret_key"))?,
+                storage::read_key(directory.join("secret/standard_deviation"))?,
+            ),
+            storage::read_key(directory.join("public/public_key"))?,
+        ))
+    }
+
+    #[cfg(not(feature = "single_key"))]
+    fn write_keys(
+        directory: &Path,
+        ((sk, std_dev_lwe), sk_out, pk): &Self::Keys,
+    ) -> Result<(), ElisabethError> {
+        storage::write_key(directory.join("secret/secret_key"), sk)?;
+        storage::write_key(directory.join("secret/standard_deviation"), std_dev_lwe)?;
+        storage::write_key(directory.join("secret/secret_key_out"), sk_out)?;
+        storage::write_key(directory.join("public/public_key"), pk)
+    }
+
+    #[cfg(feature = "single_key")]
+    fn write_keys(
+        directory: &Path,
+        ((sk, std_dev_lwe), pk): &Self::Keys,
+    ) -> Result<(), ElisabethError> {
+        storage::write_key(directory.join("secret/secret_key"), sk)?;
+        storage::write_key(directory.join("secret/standard_deviation"), std_dev_lwe)?;
+        storage::write_key(directory.join("public/public_key"), pk)
+    }
+
+    fn trivial<const BITS: usize>(
+        u: Uint<BITS>,
+        pk: &PublicKey,
+    ) -> Result<Self::Ciphertext, ElisabethError> {
+        check_width::<BITS>()?;
+        let mut output = LweCiphertext::allocate(0, pk.bsk.key_size().to_lwe_size());
+        Self::add_assign_u4(&mut output, u);
+        Ok(output)
+    }
+
+    fn encrypt<const BITS: usize>(
+        u: Uint<BITS>,
+        (sk, std_dev): &(FheSecretKey, StandardDev),
+    ) -> Result<Self::Ciphertext, ElisabethError> {
+        check_width::<BITS>()?;
+        let mut output =
+            LweCiphertext::allocate(<Torus as Numeric>::ZERO, sk.key_size().to_lwe_size());
+        let encoded = Plaintext(encode(u));
+        sk.encrypt_lwe(
+            &mut output,
+            &encoded,
+            *std_dev,
+            &mut EncryptionRandomGenerator::new(None),
+        );
+        Ok(output)
+    }
+
+    // the shifted body only has `BITS` bits
+    #[allow(clippy::cast_possible_truncation)]
+    fn decrypt<const BITS: usize>(ciphertext: &Self::Ciphertext, key: &FheSecretKey) -> Uint<BITS> {
+        let mut encoded = Plaintext(0);
+        key.decrypt_lwe(&mut encoded, ciphertext);
+        let shift = <Torus as Numeric>::BITS - BITS;
+        Uint((encoded.0.wrapping_add(1 << (shift - 1)) >> shift) as u8)
+    }
+
+    fn check_input(ciphertext: &Self::Ciphertext, pk: &PublicKey) -> Result<(), ElisabethError> {
+        let lwe_size = pk.bsk.key_size().to_lwe_size();
+        if ciphertext.lwe_size() == lwe_size {
+            Ok(())
+        } else {
+            Err(ElisabethError::ParameterMismatch(format!(
+                "the encrypted key has LWE size {}, while the public key expects {}",
+                ciphertext.lwe_size().0,
+                lwe_size.0
+            )))
+        }
+    }
+
+    fn add_assign(lhs: &mut Self::Ciphertext, rhs: &Self::Ciphertext) {
+        lhs.update_with_add(rhs);
+    }
+
+    fn add_assign_u4<const BITS: usize>(ciphertext: &mut Self::Ciphertext, u: Uint<BITS>) {
+        let body = ciphertext.get_mut_body();
+        body.0 = body.0.wrapping_add(encode(u));
+    }
+
+    fn negate(ciphertext: &mut Self::Ciphertext) {
+        ciphertext.update_with_neg();
+    }
+
+    #[cfg(not(feature = "single_key"))]
+    fn keyswitch(
+        ciphertext: &Self::Ciphertext,
+        pk: &PublicKey,
+    ) -> Result<Self::Ciphertext, ElisabethError> {
+        let ksk = if ciphertext.lwe_size() == pk.ksk.after_key_size().to_lwe_size() {
+            &pk.ksk_inv
+        } else {
+            check_lwe_size(
+                ciphertext.lwe_size(),
+                pk.ksk.before_key_size().to_lwe_size(),
+                "keyswitching key",
+            )?;
+            &pk.ksk
+        };
+        let mut res = LweCiphertext::allocate(0, ksk.after_key_size().to_lwe_size());
+        ksk.keyswitch_ciphertext(&mut res, ciphertext);
+        Ok(res)
+    }
+
+    #[cfg(feature = "single_key")]
+    fn keyswitch(
+        ciphertext: &Self::Ciphertext,
+        pk: &PublicKey,
+    ) -> Result<Self::Ciphertext, ElisabethError> {
+        check_lwe_size(
+            ciphertext.lwe_size(),
+            pk.ksk.before_key_size().to_lwe_size(),
+            "keyswitching key",
+        )?;
+        let mut res = LweCiphertext::allocate(0, pk.ksk.after_key_size().to_lwe_size());
+        pk.ksk.keyswitch_ciphertext(&mut res, ciphertext);
+        Ok(res)
+    }
+
+    /// Computes the accumulator of the bootstrapping that evaluates the S-box.
+    fn lut<const BITS: usize>(
+        table: &[Uint<BITS>],
+        pk: &PublicKey,
+    ) -> Result<Self::Lut, ElisabethError> {
+        check_width::<BITS>()?;
+        let bsk = &pk.bsk;
+        let modulus = Uint::<BITS>::MODULUS;
+        let half = modulus / 2;
+
+        let encoder = RealEncoder {
+            offset: 0.,
+            delta: f64::from(modulus),
+        };
+
+        let mut accumulator = GlweCiphertext::allocate(0, bsk.polynomial_size(), bsk.glwe_size());
+
+        for (i, res) in accumulator
+            .get_mut_body()
+            .as_mut_tensor()
+            .as_mut_slice()
+            .iter_mut()
+            .enumerate()
+        {
+            // create a valid encoding from i
+            let shift =
+                Torus::BITS as usize - 1 - (f64::log2(bsk.polynomial_size().0 as f64) as usize);
+            let encoded =
+                Plaintext(((i as Torus) << shift).round_to_closest_multiple(
+                    DecompositionBaseLog(BITS),
+                    DecompositionLevelCount(1),
+                ));
+
+            // decode the encoding
+            let decoded = encoder.decode(encoded).0 as u8;
+
+            // apply the function
+            let f_decoded = if decoded < half {
+                f64::from(table[decoded as usize].0)
+            } else {
+                f64::from((modulus - table[(decoded - half) as usize].0) % modulus)
+            };
+
+            *res = encoder.encode(Cleartext(f_decoded)).0;
+            *res = res
+                .round_to_closest_multiple(DecompositionBaseLog(BITS), DecompositionLevelCount(1));
+        }
+        Ok(accumulator)
+    }
+
+    fn bootstrap(
+        ciphertext: &Self::Ciphertext,
+        lut: &Self::Lut,
+        pk: &PublicKey,
+    ) -> Result<Self::Ciphertext, ElisabethError> {
+        let bsk = &pk.bsk;
+        check_lwe_size(
+            ciphertext.lwe_size(),
+            bsk.key_size().to_lwe_size(),
+            "bootstrapping key",
+        )?;
+        if (lut.polynomial_size(), lut.size()) != (bsk.polynomial_size(), bsk.glwe_size()) {
+            return Err(ElisabethError::ParameterMismatch(
+                "the S-box was prepared for another bootstrapping key".to_string(),
+            ));
+        }
+
+        // allocate the result
+        let mut bootstrapped_result = LweCiphertext::allocate(
+            0,
+            LweSize(bsk.glwe_size().to_glwe_dimension().0 * bsk.polynomial_size().0 + 1),
+        );
+
+        // compute the bootstrap, as `concrete_core::crypto::cross::bootstrap` but with the FFT
+        // plan and buffers of the thread: only `blind_rotate` still allocates a GLWE ciphertext
+        SCRATCH.with(|scratch| {
+            let mut scratch = scratch.borrow_mut();
+            let scratch = match &mut *scratch {
+                Some(s) if s.fits(bsk) => s,
+                s => s.insert(Scratch::new(bsk)),
+            };
+            scratch
+                .accumulator
+                .as_mut_tensor()
+                .as_mut_slice()
+                .copy_from_slice(lut.as_tensor().as_slice());
+            blind_rotate(
+                &mut scratch.fft,
+                &mut scratch.dec_i_fft,
+                &mut scratch.tmp_dec_i_fft,
+                &mut scratch.res_fft,
+                &mut scratch.accumulator,
+                ciphertext,
+                bsk,
+            );
+            constant_sample_extract(&mut bootstrapped_result, &scratch.accumulator);
+        });
+
+        Ok(bootstrapped_result)
+    }
+
+    fn zeroize(ciphertext: &mut Self::Ciphertext) {
+        ciphertext.as_mut_tensor().as_mut_slice().zeroize();
+    }
+}
@@ -0,0 +1,141 @@
+//! The homomorphic primitives `LWE` nibbles are computed with.
+//!
+//! The filter, the symmetric key and the encrypter only see nibbles. An `LWE` nibble holds a
+//! ciphertext of some `FheBackend`, which provides the few operations Elisabeth needs, and the keys
+//! they work with. `Concrete`, built on concrete-core 0.1.9, is the only backend for now, and the
+//! default one of `LWE`. The encrypter and the transcipherer are generic over the backend, while
+//! packing and debugging work on the ciphertexts of `Concrete` only.
+
+mod concrete;
+
+pub use concrete::{Concrete, PublicKey};
+
+use crate::{error::ElisabethError, nibble::Uint, SystemParameters};
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::Path;
+
+/// A library of LWE ciphertexts encrypting nibbles, of any supported width, with keyswitchings and
+/// programmable bootstrappings.
+///
+/// Ciphertexts are either under the input key of the bootstrappings, as the encrypted symmetric
+/// key, or under their output key. Keyswitchings go from one to the other, or back to the input key
+/// in single key mode.
+pub trait FheBackend: 'static {
+    /// An LWE ciphertext of a nibble.
+    type Ciphertext: Clone + Send + Sync + Serialize + DeserializeOwned;
+    /// An LWE secret key, such as the input and output keys of the bootstrappings.
+    type SecretKey;
+    /// The material needed to encrypt a nibble under the input key of the bootstrappings.
+    type EncryptionKey;
+    /// The bootstrapping and keyswitching keys.
+    type PublicKey: Send + Sync;
+    /// A lookup table, prepared for the bootstrappings of a given public key.
+    type Lut: Send + Sync;
+    /// Everything `generate_keys` outputs.
+    type Keys;
+
+    /// Generates new secret keys and a public key for the given preset.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParameterMismatch` if the backend does not support the preset.
+    fn generate_keys(params: &SystemParameters) -> Result<Self::Keys, ElisabethError>;
+
+    /// Reads the keys written by `write_keys` in `directory`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MissingKey` or `CorruptKey` if a key can not be read.
+    fn read_keys(directory: &Path) -> Result<Self::Keys, ElisabethError>;
+
+    /// Writes the keys in `directory`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Io` or `Serialization` if a key can not be written.
+    fn write_keys(directory: &Path, keys: &Self::Keys) -> Result<(), ElisabethError>;
+
+    /// Returns a trivial encryption of `u` under the input key of the bootstrappings.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the backend can not encrypt a nibble of `BITS` bits.
+    fn trivial<const BITS: usize>(
+        u: Uint<BITS>,
+        pk: &Self::PublicKey,
+    ) -> Result<Self::Ciphertext, ElisabethError>;
+
+    /// Encrypts `u` under the input key of the bootstrappings.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the backend can not encrypt a nibble of `BITS` bits.
+    fn encrypt<const BITS: usize>(
+        u: Uint<BITS>,
+        key: &Self::EncryptionKey,
+    ) -> Result<Self::Ciphertext, ElisabethError>;
+
+    /// Decrypts a nibble of `BITS` bits, rounding away the noise.
+    fn decrypt<const BITS: usize>(
+        ciphertext: &Self::Ciphertext,
+        key: &Self::SecretKey,
+    ) -> Uint<BITS>;
+
+    /// Checks that the ciphertext is under the input key of the bootstrappings of `pk`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParameterMismatch` if it is not.
+    fn check_input(
+        ciphertext: &Self::Ciphertext,
+        pk: &Self::PublicKey,
+    ) -> Result<(), ElisabethError>;
+
+    fn add_assign(lhs: &mut Self::Ciphertext, rhs: &Self::Ciphertext);
+
+    /// Adds a clear nibble to the ciphertext.
+    fn add_assign_u4<const BITS: usize>(ciphertext: &mut Self::Ciphertext, u: Uint<BITS>);
+
+    fn negate(ciphertext: &mut Self::Ciphertext);
+
+    /// Moves the ciphertext to the other key.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParameterMismatch` if the ciphertext is under neither key of `pk`.
+    fn keyswitch(
+        ciphertext: &Self::Ciphertext,
+        pk: &Self::PublicKey,
+    ) -> Result<Self::Ciphertext, ElisabethError>;
+
+    /// Prepares the lookup table of a negacyclic S-box, of `2^BITS` entries.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the bootstrappings of `pk` can not evaluate the S-box.
+    fn lut<const BITS: usize>(
+        table: &[Uint<BITS>],
+        pk: &Self::PublicKey,
+    ) -> Result<Self::Lut, ElisabethError>;
+
+    /// Applies the lookup table to a ciphertext under the input key of the bootstrappings. The
+    /// output is under their output key.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParameterMismatch` if the ciphertext or the lookup table do not match `pk`.
+    fn bootstrap(
+        ciphertext: &Self::Ciphertext,
+        lut: &Self::Lut,
+        pk: &Self::PublicKey,
+    ) -> Result<Self::Ciphertext, ElisabethError>;
+
+    /// Wipes the ciphertext from memory.
+    fn zeroize(ciphertext: &mut Self::Ciphertext);
+}
+
+/// A key of some backend, which lets the backend be inferred from the keys given to generic
+/// functions such as `Encrypter::export_encrypted_key` or `Calibration::measure`.
+pub trait BackendKey {
+    type Backend: FheBackend;
+}
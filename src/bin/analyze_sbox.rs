@@ -0,0 +1,87 @@
+use elisabeth::{
+    analysis::{analyze_filter_block, analyze_sbox, Properties},
+    u4, FilterSpec,
+};
+use std::{convert::TryInto, env, process};
+
+fn print_properties(properties: &Properties) {
+    println!(
+        "  differential uniformity: {} (bitwise), {} (modulo 16)",
+        properties.differential_uniformity, properties.additive_differential_uniformity
+    );
+    println!("  linearity: {}", properties.linearity);
+    println!("  algebraic degree: {}", properties.algebraic_degree);
+    println!("  balanced: {}", properties.balanced);
+}
+
+fn print_sbox(name: &str, sbox: &[u4; 16]) {
+    let report = analyze_sbox(sbox);
+    println!(
+        "{}: {}",
+        name,
+        sbox.iter()
+            .map(|u| format!("{:X}", u.0))
+            .collect::<String>()
+    );
+    print_properties(&report.properties);
+    println!("  fixed points: {}", report.fixed_points);
+    println!("  bijective: {}", report.bijective);
+    println!("  negacyclic: {}", report.negacyclic);
+}
+
+/// Parses an S-box written as 16 hexadecimal digits, `S(0)` first.
+fn parse_sbox(table: &str) -> Option<[u4; 16]> {
+    let digits = table
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| u4(d as u8)))
+        .collect::<Option<Vec<_>>>()?;
+    digits.try_into().ok()
+}
+
+/// Analyzes the S-boxes given as arguments, or the S-boxes and the block of Elisabeth-4 if none
+/// is given.
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if !args.is_empty() {
+        for arg in &args {
+            match parse_sbox(arg) {
+                Some(sbox) => print_sbox("S-box", &sbox),
+                None => {
+                    eprintln!("{} is not an S-box of 16 hexadecimal digits", arg);
+                    process::exit(1);
+                }
+            }
+        }
+        return;
+    }
+
+    let spec = FilterSpec::elisabeth4();
+    for (layer, sboxes) in [(1, &spec.first_layer), (2, &spec.second_layer)] {
+        for (i, sbox) in sboxes.iter().enumerate() {
+            print_sbox(&format!("layer {} S-box {}", layer, i), sbox);
+        }
+    }
+
+    println!("block of {} nibbles:", spec.block_width);
+    match analyze_filter_block(&spec) {
+        Ok(report) => {
+            print_properties(&report.properties);
+            for (j, (bitwise, additive)) in report
+                .differential_uniformity_by_nibble
+                .iter()
+                .zip(report.additive_differential_uniformity_by_nibble.iter())
+                .enumerate()
+            {
+                println!(
+                    "  differential uniformity on nibble {}: {} (bitwise), {} (modulo 16)",
+                    j, bitwise, additive
+                );
+            }
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Bitsliced evaluation of the filter on clear nibbles.
+//!
+//! `LANES` positions of the keystream are computed at once: the bit `b` of the nibble of the
+//! position `k` of a batch is the bit `k` of the word `b`, out of one word per bit of the nibbles.
+//! Additions are evaluated with a ripple-carry adder and S-boxes with their algebraic normal form,
+//! so that a whole batch costs about as many word operations as a single nibble.
+
+use crate::{
+    error::ElisabethError,
+    filter::{Filter, FilterSpec},
+    nibble::{Uint, Width},
+    symmetric_key::{Nonce, SymmetricKey},
+};
+#[cfg(feature = "multithread")]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use zeroize::{Zeroize, Zeroizing};
+
+/// The number of keystream nibbles computed per batch.
+pub const LANES: usize = 64;
+
+/// `LANES` nibbles, one per bit of each word, the least significant bit first.
+#[derive(Clone, Copy)]
+struct Sliced<const BITS: usize>([u64; BITS]);
+
+impl<const BITS: usize> Default for Sliced<BITS> {
+    fn default() -> Self {
+        Self([0; BITS])
+    }
+}
+
+impl<const BITS: usize> Zeroize for Sliced<BITS> {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl<const BITS: usize> Sliced<BITS> {
+    /// Slices the given nibbles, the first one going to the lane 0.
+    fn new(nibbles: impl Iterator<Item = Uint<BITS>>) -> Self {
+        let mut res = Self::default();
+        for (lane, u) in nibbles.enumerate() {
+            for (b, word) in res.0.iter_mut().enumerate() {
+                *word |= u64::from((u.0 >> b) & 1) << lane;
+            }
+        }
+        res
+    }
+
+    /// Returns the nibble of the given lane.
+    fn nibble(&self, lane: usize) -> Uint<BITS> {
+        Uint(
+            self.0
+                .iter()
+                .enumerate()
+                .fold(0, |u, (b, word)| u | ((((word >> lane) & 1) as u8) << b)),
+        )
+    }
+
+    /// Adds two batches of nibbles modulo `2^BITS`.
+    fn add(self, rhs: Self) -> Self {
+        let (a, b) = (self.0, rhs.0);
+        let mut res = [0; BITS];
+        let mut carry = 0;
+        for i in 0..BITS {
+            res[i] = a[i] ^ b[i] ^ carry;
+            carry = (a[i] & b[i]) | (carry & (a[i] ^ b[i]));
+        }
+        Self(res)
+    }
+}
+
+/// An S-box in algebraic normal form: the bit `m` of the word `j` is the coefficient, in the
+/// output bit `j`, of the product of the input bits set in `m`.
+struct Anf<const BITS: usize>([u64; BITS]);
+
+impl<const BITS: usize> Anf<BITS> {
+    /// The number of entries of an S-box, and of monomials of its algebraic normal form.
+    const SIZE: usize = 1 << BITS;
+
+    fn new(sbox: &[Uint<BITS>]) -> Self {
+        let mut anf = [0_u64; BITS];
+        for (j, coefficients) in anf.iter_mut().enumerate() {
+            let mut truth_table = (0..Self::SIZE)
+                .map(|x| (sbox[x].0 >> j) & 1)
+                .collect::<Vec<_>>();
+            for i in 0..BITS {
+                for x in 0..Self::SIZE {
+                    if x & (1 << i) != 0 {
+                        truth_table[x] ^= truth_table[x ^ (1 << i)];
+                    }
+                }
+            }
+            *coefficients = truth_table
+                .iter()
+                .enumerate()
+                .fold(0, |c, (m, &t)| c | (u64::from(t) << m));
+        }
+        Self(anf)
+    }
+
+    fn apply(&self, x: Sliced<BITS>) -> Sliced<BITS> {
+        let mut monomials = [!0_u64; 64];
+        for m in 1..Self::SIZE {
+            let highest = (usize::BITS - 1 - m.leading_zeros()) as usize;
+            monomials[m] = monomials[m ^ (1 << highest)] & x.0[highest];
+        }
+
+        let mut res = [0; BITS];
+        for (word, &coefficients) in res.iter_mut().zip(self.0.iter()) {
+            for (m, monomial) in monomials[..Self::SIZE].iter().enumerate() {
+                *word ^= monomial & 0_u64.wrapping_sub((coefficients >> m) & 1);
+            }
+        }
+        Sliced(res)
+    }
+}
+
+/// The S-boxes of a filter, in algebraic normal form.
+struct SlicedFilter<'a, const BITS: usize>
+where
+    Uint<BITS>: Width,
+{
+    spec: &'a FilterSpec<BITS>,
+    first_layer: Vec<Anf<BITS>>,
+    second_layer: Vec<Anf<BITS>>,
+}
+
+impl<'a, const BITS: usize> SlicedFilter<'a, BITS>
+where
+    Uint<BITS>: Width,
+{
+    fn new(filter: &'a Filter<BITS>) -> Self {
+        let spec = filter.spec();
+        let anf = |layer: &[<Uint<BITS> as Width>::Table]| {
+            layer.iter().map(|sbox| Anf::new(sbox.as_ref())).collect()
+        };
+        Self {
+            spec,
+            first_layer: anf(&spec.first_layer),
+            second_layer: anf(&spec.second_layer),
+        }
+    }
+
+    /// Computes the output of a block, as `Filter::filter_block`.
+    fn block(&self, x: &[Sliced<BITS>]) -> Sliced<BITS> {
+        let FilterSpec {
+            block_width,
+            first_wiring,
+            second_wiring,
+            ..
+        } = self.spec;
+
+        let first_layer_output = Zeroizing::new(
+            (0..block_width - 1)
+                .map(|i| self.first_layer[i].apply(x[i].add(x[first_wiring[i]])))
+                .collect::<Vec<_>>(),
+        );
+
+        (0..block_width - 1).fold(x[block_width - 1], |acc, i| {
+            let input = second_wiring[i]
+                .iter()
+                .fold(x[i], |sum, &j| sum.add(first_layer_output[j]));
+            acc.add(self.second_layer[i].apply(input))
+        })
+    }
+
+    /// Computes `count` nibbles of the keystream selected by `nonce`, starting at `offset`.
+    fn batch(
+        &self,
+        key: &SymmetricKey<Uint<BITS>>,
+        nonce: Nonce,
+        offset: u64,
+        count: usize,
+    ) -> Result<Vec<Uint<BITS>>, ElisabethError> {
+        let rounds = (offset..offset + count as u64)
+            .map(|counter| key.round(nonce, counter))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let whitened_subset = Zeroizing::new(
+            (0..key.round_size())
+                .map(|p| {
+                    let key_nibbles = Sliced::new(rounds.iter().map(|r| key.key()[r.indices[p]]));
+                    key_nibbles.add(Sliced::new(rounds.iter().map(|r| r.whitening[p])))
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        let output = Zeroizing::new(
+            whitened_subset
+                .chunks(self.spec.block_width)
+                .fold(Sliced::default(), |acc, block| acc.add(self.block(block))),
+        );
+        Ok((0..count).map(|lane| output.nibble(lane)).collect())
+    }
+}
+
+/// Computes the nibbles `offset..offset + len` of the keystream selected by `nonce`.
+#[cfg(not(feature = "multithread"))]
+pub fn keystream<const BITS: usize>(
+    filter: &Filter<BITS>,
+    key: &SymmetricKey<Uint<BITS>>,
+    nonce: Nonce,
+    offset: u64,
+    len: usize,
+) -> Result<Vec<Uint<BITS>>, ElisabethError>
+where
+    Uint<BITS>: Width,
+{
+    let filter = SlicedFilter::new(filter);
+    let batches = (0..len)
+        .step_by(LANES)
+        .map(|start| filter.batch(key, nonce, offset + start as u64, LANES.min(len - start)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(batches.concat())
+}
+
+/// Computes the nibbles `offset..offset + len` of the keystream selected by `nonce`, one batch
+/// per task.
+#[cfg(feature = "multithread")]
+pub fn keystream<const BITS: usize>(
+    filter: &Filter<BITS>,
+    key: &SymmetricKey<Uint<BITS>>,
+    nonce: Nonce,
+    offset: u64,
+    len: usize,
+) -> Result<Vec<Uint<BITS>>, ElisabethError>
+where
+    Uint<BITS>: Width,
+{
+    let filter = SlicedFilter::new(filter);
+    let batches = (0..len.div_ceil(LANES))
+        .into_par_iter()
+        .map(|batch| {
+            let start = batch * LANES;
+            filter.batch(key, nonce, offset + start as u64, LANES.min(len - start))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(batches.concat())
+}
@@ -0,0 +1,315 @@
+//! Cost of the homomorphic evaluation of Elisabeth.
+//!
+//! The number of operations needed per transciphered nibble only depends on the structure of the
+//! filter, and is given by `SystemParameters::cost`. Along with the time taken by each operation,
+//! measured once on the target machine with `Calibration::measure`, it gives an estimate of the
+//! time and memory needed to transcipher a message with `SystemParameters::estimate`.
+
+use crate::{
+    backend::{BackendKey, FheBackend},
+    error::ElisabethError,
+    filter::FilterSpec,
+    nibble::Nibble,
+    u4, SystemParameters, LWE,
+};
+use std::{
+    mem,
+    time::{Duration, Instant},
+};
+
+/// The number of homomorphic operations needed to compute some output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cost {
+    /// Programmable bootstrappings, one per S-box.
+    pub bootstraps: usize,
+    /// Keyswitchings with `ksk`, from the output key of the bootstrappings to their input key.
+    pub keyswitches: usize,
+    /// Keyswitchings with `ksk_inv`, from the input key of the bootstrappings to their output key.
+    pub inverse_keyswitches: usize,
+    /// Additions of two LWE ciphertexts.
+    pub additions: usize,
+    /// Negations of an LWE ciphertext.
+    pub negations: usize,
+    /// Additions of a clear nibble to the body of an LWE ciphertext. They stand for additions of a
+    /// trivial encryption, which never has to be allocated.
+    pub plaintext_additions: usize,
+    /// The highest number of bootstrappings on a path from the input to the output.
+    pub bootstrap_depth: usize,
+    /// The highest number of keyswitchings on a path from the input to the output.
+    pub keyswitch_depth: usize,
+}
+
+impl Cost {
+    /// Returns the cost of `times` independent computations of this cost, the depth being kept.
+    const fn repeat(self, times: usize) -> Self {
+        Self {
+            bootstraps: self.bootstraps * times,
+            keyswitches: self.keyswitches * times,
+            inverse_keyswitches: self.inverse_keyswitches * times,
+            additions: self.additions * times,
+            negations: self.negations * times,
+            plaintext_additions: self.plaintext_additions * times,
+            ..self
+        }
+    }
+
+    /// Returns the time taken by these operations on a single core.
+    #[must_use]
+    pub fn time(&self, calibration: &Calibration) -> Duration {
+        calibration.bootstrap * self.bootstraps as u32
+            + calibration.keyswitch * self.keyswitches as u32
+            + calibration.inverse_keyswitch * self.inverse_keyswitches as u32
+            + calibration.addition * self.additions as u32
+            + calibration.negation * self.negations as u32
+            + calibration.plaintext_addition * self.plaintext_additions as u32
+    }
+}
+
+impl FilterSpec {
+    /// Returns the cost of the evaluation of one block of the filter, whitening included.
+    #[must_use]
+    pub fn block_cost(&self) -> Cost {
+        let lanes = self.block_width - 1;
+        let second_layer_additions = self.second_wiring.iter().map(Vec::len).sum::<usize>();
+
+        Cost {
+            bootstraps: 2 * lanes,
+            // the sums of the first layer outputs go back to the input key of the bootstrapping,
+            // as does the output of the block in single key mode
+            keyswitches: if cfg!(feature = "single_key") {
+                lanes + 1
+            } else {
+                lanes
+            },
+            // otherwise, the last nibble of the block goes to the output key of the bootstrapping
+            inverse_keyswitches: usize::from(!cfg!(feature = "single_key")),
+            // the inputs of both layers, the sum of the lanes, and the last nibble
+            additions: lanes + second_layer_additions + lanes - 1 + 1,
+            negations: 0,
+            plaintext_additions: self.block_width,
+            bootstrap_depth: 2,
+            keyswitch_depth: if cfg!(feature = "single_key") { 2 } else { 1 },
+        }
+    }
+}
+
+impl SystemParameters {
+    /// Returns the cost of one block, the number of blocks, and the cost of what remains to
+    /// transcipher one nibble once the blocks are evaluated.
+    fn block_costs(&self) -> (Cost, usize, Cost) {
+        let parameters = self.parameters();
+        let spec = parameters.filter.spec();
+        let blocks = parameters.n / spec.block_width;
+        let rest = Cost {
+            // the sum of the blocks
+            additions: blocks - 1,
+            // the keystream is negated and the ciphertext added to it
+            negations: 1,
+            plaintext_additions: 1,
+            ..Cost::default()
+        };
+        (spec.block_cost(), blocks, rest)
+    }
+
+    /// Returns the cost of the transciphering of one nibble.
+    #[must_use]
+    pub fn cost(&self) -> Cost {
+        let (block, blocks, rest) = self.block_costs();
+        let cost = block.repeat(blocks);
+        Cost {
+            additions: cost.additions + rest.additions,
+            negations: cost.negations + rest.negations,
+            plaintext_additions: cost.plaintext_additions + rest.plaintext_additions,
+            ..cost
+        }
+    }
+
+    /// Estimates the time and memory needed by a `Transcipherer` to transcipher `nibbles` nibbles
+    /// on `cores` cores, given the time taken by each operation on one of them.
+    ///
+    /// The keystream is computed by windows of `cores` nibbles, the blocks of which are spread
+    /// over the cores. The memory accounts for the public key, the encrypted symmetric key, the
+    /// output and the ciphertexts alive during the evaluation of a window, but not for the buffers
+    /// allocated by the FFT.
+    #[must_use]
+    pub fn estimate(&self, calibration: &Calibration, nibbles: usize, cores: usize) -> Estimate {
+        let cores = cores.max(1);
+        let (block, blocks, rest) = self.block_costs();
+        let window_time = |window: usize| {
+            let rounds = |tasks: usize| tasks.div_ceil(cores) as u32;
+            block.time(calibration) * rounds(window * blocks)
+                + rest.time(calibration) * rounds(window)
+        };
+        let time = window_time(cores) * (nibbles / cores) as u32 + window_time(nibbles % cores);
+
+        let parameters = self.parameters();
+        let block_width = parameters.filter.spec().block_width;
+        let sizes = FheSizes::new(self);
+        let torus = mem::size_of::<crate::Torus>();
+        let memory = sizes.public_key
+            + parameters.key_size * sizes.input * torus
+            + nibbles * sizes.output * torus
+            // the outputs of the blocks of a window
+            + cores.min(nibbles) * blocks * sizes.output * torus
+            // what a task holds while evaluating a block: its key nibbles, the outputs of the
+            // first layer, and the accumulator and output of a bootstrapping
+            + cores
+                * (block_width * sizes.input
+                    + block_width * sizes.bootstrapped
+                    + sizes.accumulator)
+                * torus;
+
+        Estimate { time, memory }
+    }
+}
+
+/// The predicted cost of a transciphering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Estimate {
+    pub time: Duration,
+    /// In bytes.
+    pub memory: usize,
+}
+
+/// The time taken by each operation on one core of the target machine.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Calibration {
+    pub bootstrap: Duration,
+    pub keyswitch: Duration,
+    pub inverse_keyswitch: Duration,
+    pub addition: Duration,
+    pub negation: Duration,
+    pub plaintext_addition: Duration,
+}
+
+impl Calibration {
+    /// Measures the average time of each operation over `runs` runs, with the given public key of
+    /// any FHE backend. The operations are applied to trivial encryptions, which take as long as
+    /// any other.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of an operation, if the public key can not evaluate the S-boxes of
+    /// Elisabeth-4.
+    pub fn measure<P: BackendKey>(public_key: &P, runs: usize) -> Result<Self, ElisabethError>
+    where
+        P::Backend: FheBackend<PublicKey = P>,
+    {
+        let runs = runs.max(1);
+        let pk = public_key;
+        let sbox = LWE::<P::Backend>::sbox(&FilterSpec::elisabeth4().first_layer[0], pk)?;
+
+        let input = LWE::<P::Backend>::trivial(u4(1), pk)?;
+        // a ciphertext under the output key of the bootstrapping
+        let output = input.apply_sbox(&sbox, pk)?;
+
+        let time = |f: &mut dyn FnMut() -> Result<(), ElisabethError>| {
+            let start = Instant::now();
+            for _ in 0..runs {
+                f()?;
+            }
+            Ok::<_, ElisabethError>(start.elapsed() / runs as u32)
+        };
+
+        let mut nibble = output.clone();
+        Ok(Self {
+            bootstrap: time(&mut || input.apply_sbox(&sbox, pk).map(drop))?,
+            // keyswitching a nibble under the output key of the bootstrapping uses `ksk`
+            keyswitch: time(&mut || output.clone().keyswitch(pk))?,
+            #[cfg(not(feature = "single_key"))]
+            inverse_keyswitch: time(&mut || input.clone().keyswitch(pk))?,
+            #[cfg(feature = "single_key")]
+            inverse_keyswitch: Duration::default(),
+            addition: time(&mut || {
+                nibble.add_assign(&output);
+                Ok(())
+            })?,
+            negation: time(&mut || {
+                nibble.negate();
+                Ok(())
+            })?,
+            plaintext_addition: time(&mut || {
+                nibble.add_assign_u4(&u4(1));
+                Ok(())
+            })?,
+        })
+    }
+}
+
+/// The sizes, in torus elements, of the keys and ciphertexts of a preset.
+struct FheSizes {
+    /// The size of the public key, in bytes.
+    public_key: usize,
+    /// An LWE ciphertext under the input key of the bootstrapping.
+    input: usize,
+    /// An LWE ciphertext under the output key of the bootstrapping.
+    bootstrapped: usize,
+    /// A transciphered nibble.
+    output: usize,
+    /// The GLWE accumulator of a bootstrapping.
+    accumulator: usize,
+}
+
+impl FheSizes {
+    #[cfg(not(feature = "single_key"))]
+    fn new(params: &SystemParameters) -> Self {
+        let (
+            (lwe_dimension, _),
+            (glwe_dimension, polynomial_size, _),
+            (_, level_bs),
+            (_, level_ks),
+            (_, level_ks_inv),
+        ) = params.fhe_parameters();
+
+        let input = lwe_dimension.0 + 1;
+        let bootstrapped = glwe_dimension.0 * polynomial_size.0 + 1;
+        let glwe_size = glwe_dimension.0 + 1;
+        let torus = mem::size_of::<crate::Torus>();
+        let bsk = lwe_dimension.0
+            * level_bs.0
+            * glwe_size
+            * glwe_size
+            * polynomial_size.0
+            * mem::size_of::<concrete_core::math::fft::Complex64>();
+        let ksk = (bootstrapped - 1) * level_ks.0 * input * torus;
+        let ksk_inv = lwe_dimension.0 * level_ks_inv.0 * bootstrapped * torus;
+
+        Self {
+            public_key: bsk + ksk + ksk_inv,
+            input,
+            bootstrapped,
+            output: bootstrapped,
+            accumulator: glwe_size * polynomial_size.0,
+        }
+    }
+
+    #[cfg(feature = "single_key")]
+    fn new(params: &SystemParameters) -> Self {
+        let (
+            (lwe_dimension, _),
+            (glwe_dimension, polynomial_size, _),
+            (_, level_bs),
+            (_, level_ks),
+        ) = params.fhe_parameters();
+
+        let input = lwe_dimension.0 + 1;
+        let bootstrapped = glwe_dimension.0 * polynomial_size.0 + 1;
+        let glwe_size = glwe_dimension.0 + 1;
+        let torus = mem::size_of::<crate::Torus>();
+        let bsk = lwe_dimension.0
+            * level_bs.0
+            * glwe_size
+            * glwe_size
+            * polynomial_size.0
+            * mem::size_of::<concrete_core::math::fft::Complex64>();
+        let ksk = (bootstrapped - 1) * level_ks.0 * input * torus;
+
+        Self {
+            public_key: bsk + ksk,
+            input,
+            bootstrapped,
+            output: input,
+            accumulator: glwe_size * polynomial_size.0,
+        }
+    }
+}
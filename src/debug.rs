@@ -0,0 +1,294 @@
+//! Location of the homomorphic operations that go wrong.
+//!
+//! A `DebugNibble` is an `LWE` nibble that carries, along with its ciphertext, the clear nibble it
+//! should encrypt: its shadow, which goes through the same operations in the clear. After every
+//! bootstrapping, keyswitching and addition, the ciphertext is decrypted with a `DebugKey` and
+//! compared to its shadow. The first operation after which they differ is recorded, with the noise
+//! measured at that point, and can be read with `DebugKey::divergence`.
+//!
+//! Decrypting after each operation is slow, and the secret keys have to be at hand, so that this is
+//! only meant to investigate the errors reported by `tests/homomorphic.rs`. Like the presets, it
+//! only works on nibbles of 4 bits, the widest that the FHE parameters support.
+
+use crate::{
+    backend::{Concrete, FheBackend, PublicKey},
+    error::ElisabethError,
+    nibble::Nibble,
+    secret::FheSecretKey,
+    u4, Encrypter, Nonce, Torus, LWE,
+};
+use concrete_commons::StandardDev;
+use concrete_core::crypto::encoding::Plaintext;
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex, PoisonError,
+};
+use zeroize::Zeroize;
+
+/// A checked homomorphic operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// A bootstrapping, with the table of the S-box of 4 bits it evaluates.
+    Sbox([u8; 16]),
+    Keyswitch,
+    Add,
+}
+
+/// The first operation after which a ciphertext did not decrypt to its shadow.
+#[derive(Clone, Copy, Debug)]
+pub struct Divergence {
+    pub operation: Operation,
+    /// The number of operations checked before this one, by all the threads.
+    pub index: u64,
+    /// The shadow of the output.
+    pub expected: u4,
+    /// The decryption of the output.
+    pub decrypted: u4,
+    /// The noise of the output with respect to its shadow, on the torus, in `[-1/2, 1/2)`.
+    pub noise: f64,
+}
+
+impl Divergence {
+    /// Returns the base 2 logarithm of the absolute value of the noise.
+    #[must_use]
+    pub fn log2_noise(&self) -> f64 {
+        self.noise.abs().log2()
+    }
+}
+
+struct Keys {
+    input: (FheSecretKey, StandardDev),
+    output: Option<FheSecretKey>,
+    checked: AtomicU64,
+    divergence: Mutex<Option<Divergence>>,
+}
+
+/// The secret keys the nibbles are decrypted with, and the record of the first divergence.
+/// Clones share the record.
+#[derive(Clone)]
+pub struct DebugKey(Arc<Keys>);
+
+impl DebugKey {
+    /// Builds a debug key from the keys output by `SystemParameters::generate_fhe_keys`.
+    #[cfg(not(feature = "single_key"))]
+    #[must_use]
+    pub fn new(input: (FheSecretKey, StandardDev), output: FheSecretKey) -> Self {
+        Self::with_keys(input, Some(output))
+    }
+
+    /// Builds a debug key from the keys output by `SystemParameters::generate_fhe_keys`.
+    ///
+    /// The output key of the bootstrappings is not among them in single key mode, so that the
+    /// outputs of the bootstrappings are only checked once keyswitched back to the input key.
+    #[cfg(feature = "single_key")]
+    #[must_use]
+    pub fn new(input: (FheSecretKey, StandardDev)) -> Self {
+        Self::with_keys(input, None)
+    }
+
+    fn with_keys(input: (FheSecretKey, StandardDev), output: Option<FheSecretKey>) -> Self {
+        Self(Arc::new(Keys {
+            input,
+            output,
+            checked: AtomicU64::new(0),
+            divergence: Mutex::new(None),
+        }))
+    }
+
+    /// Returns the first operation after which a ciphertext did not decrypt to its shadow, if any.
+    /// With the `multithread` feature, operations are checked in the order in which they end, so
+    /// that this is the first divergence found rather than the first in the evaluation order.
+    #[must_use]
+    pub fn divergence(&self) -> Option<Divergence> {
+        *self
+            .0
+            .divergence
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the number of operations checked so far.
+    #[must_use]
+    pub fn checked(&self) -> u64 {
+        self.0.checked.load(Ordering::Relaxed)
+    }
+
+    /// Decrypts the ciphertext and compares it to its shadow, recording the first divergence.
+    fn check(&self, nibble: &DebugNibble, operation: Operation) {
+        let lwe = nibble.lwe.as_lwe();
+        let key = if lwe.lwe_size() == self.0.input.0.key_size().to_lwe_size() {
+            &self.0.input.0
+        } else if let Some(key) = &self.0.output {
+            key
+        } else {
+            return;
+        };
+        let mut encoded = Plaintext(0);
+        key.decrypt_lwe(&mut encoded, lwe);
+
+        let index = self.0.checked.fetch_add(1, Ordering::Relaxed);
+        // a nibble of 4 bits is encoded in the 4 most significant bits of the torus
+        let decrypted = u4((encoded.0.wrapping_add(1 << 59) >> 60) as u8);
+        if decrypted.0 == nibble.shadow.0 {
+            return;
+        }
+        // the error is read as a centered signed integer, of which only the magnitude is reported
+        #[allow(clippy::cast_possible_wrap, clippy::cast_precision_loss)]
+        let noise = encoded.0.wrapping_sub(Torus::from(nibble.shadow.0) << 60) as i64 as f64
+            / f64::exp2(64.);
+        let mut divergence = self
+            .0
+            .divergence
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if divergence.is_none() {
+            *divergence = Some(Divergence {
+                operation,
+                index,
+                expected: nibble.shadow,
+                decrypted,
+                noise,
+            });
+        }
+    }
+}
+
+/// The material needed to compute on debug nibbles.
+pub struct DebugContext {
+    pub public_key: PublicKey,
+    pub key: DebugKey,
+}
+
+/// An `LWE` nibble along with the clear nibble it should encrypt.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DebugNibble {
+    lwe: LWE,
+    shadow: u4,
+    /// A deserialized nibble is no longer checked.
+    #[serde(skip)]
+    key: Option<DebugKey>,
+}
+
+impl DebugNibble {
+    /// Returns the ciphertext.
+    #[must_use]
+    pub const fn lwe(&self) -> &LWE {
+        &self.lwe
+    }
+
+    /// Returns the clear nibble the ciphertext should encrypt.
+    #[must_use]
+    pub const fn shadow(&self) -> u4 {
+        self.shadow
+    }
+
+    fn check(&self, operation: Operation) {
+        if let Some(key) = &self.key {
+            key.check(self, operation);
+        }
+    }
+}
+
+impl Encrypter<DebugNibble> {
+    /// Transcrypts the given symmetric ciphertext, encrypted under `nonce`, checking every
+    /// operation on the way.
+    ///
+    /// # Errors
+    ///
+    /// Fails as `Encrypter::decrypt`, a divergence is not an error.
+    pub fn transcipher(
+        &self,
+        nonce: Nonce,
+        ciphertext: &[u4],
+    ) -> Result<Vec<DebugNibble>, ElisabethError> {
+        self.decrypt_to_vec(nonce, 0, ciphertext)
+    }
+}
+
+/// Both the ciphertext and its shadow are secret.
+impl Zeroize for DebugNibble {
+    fn zeroize(&mut self) {
+        self.lwe.zeroize();
+        self.shadow.zeroize();
+    }
+}
+
+impl Nibble for DebugNibble {
+    type EncryptionContext = DebugKey;
+    type EvaluationContext = DebugContext;
+
+    fn trivial(u: u4, context: &DebugContext) -> Result<Self, ElisabethError> {
+        Ok(Self {
+            lwe: <LWE>::trivial(u, &context.public_key)?,
+            shadow: u,
+            key: Some(context.key.clone()),
+        })
+    }
+
+    fn encrypt(u: u4, key: &DebugKey) -> Result<Self, ElisabethError> {
+        Ok(Self {
+            lwe: <LWE>::encrypt(u, &key.0.input)?,
+            shadow: u,
+            key: Some(key.clone()),
+        })
+    }
+
+    /// The lookup table, and the table it is built from.
+    type Sbox = (<Concrete as FheBackend>::Lut, <u4 as Nibble>::Sbox);
+
+    fn sbox(table: &[u4], context: &DebugContext) -> Result<Self::Sbox, ElisabethError> {
+        Ok((
+            <LWE>::sbox(table, &context.public_key)?,
+            u4::sbox(table, &())?,
+        ))
+    }
+
+    fn apply_sbox(
+        &self,
+        (lut, table): &Self::Sbox,
+        context: &DebugContext,
+    ) -> Result<Self, ElisabethError> {
+        let output = Self {
+            lwe: self.lwe.apply_sbox(lut, &context.public_key)?,
+            shadow: self.shadow.apply_sbox(table, &())?,
+            key: self.key.clone(),
+        };
+        output.check(Operation::Sbox(table.map(|u| u.0)));
+        Ok(output)
+    }
+
+    fn negate(&mut self) {
+        self.lwe.negate();
+        self.shadow.negate();
+    }
+
+    fn keyswitch(&mut self, context: &DebugContext) -> Result<(), ElisabethError> {
+        self.lwe.keyswitch(&context.public_key)?;
+        self.check(Operation::Keyswitch);
+        Ok(())
+    }
+
+    fn add(&self, rhs: &Self) -> Self {
+        let mut output = self.clone();
+        output.add_assign(rhs);
+        output
+    }
+
+    fn add_u4(&self, rhs: &u4) -> Self {
+        let mut output = self.clone();
+        output.add_assign_u4(rhs);
+        output
+    }
+
+    fn add_assign(&mut self, rhs: &Self) {
+        self.lwe.add_assign(&rhs.lwe);
+        self.shadow.add_assign(&rhs.shadow);
+        self.check(Operation::Add);
+    }
+
+    fn add_assign_u4(&mut self, rhs: &u4) {
+        self.lwe.add_assign_u4(rhs);
+        self.shadow.add_assign_u4(rhs);
+    }
+}
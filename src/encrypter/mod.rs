@@ -1,135 +1,431 @@
 pub mod parameters;
-
-use std::{env, fs, path::Path};
+mod usage;
 
 use crate::{
-    filter::Filter, nibble::Nibble, public_key::PublicKey, symmetric_key::SymmetricKey, u4,
+    backend::{BackendKey, FheBackend},
+    error::ElisabethError,
+    filter::{Filter, Sboxes},
+    nibble::{Nibble, Uint, Width},
+    storage,
+    symmetric_key::{Nonce, SymmetricKey},
+    LWE,
+};
+use parameters::{ParameterSet, Parameters};
+#[cfg(feature = "multithread")]
+use rayon::{
+    iter::{IntoParallelIterator, ParallelExtend, ParallelIterator},
+    slice::ParallelSlice,
 };
-use concrete_core::{crypto::secret::LweSecretKey, math::random::RandomGenerator};
-use parameters::{Parameters, SystemParameters};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use usage::Usage;
+use zeroize::Zeroizing;
 
-pub struct Encrypter<T: Nibble> {
+pub struct Encrypter<T: Nibble<BITS>, const BITS: usize = 4>
+where
+    Uint<BITS>: Width,
+{
     symmetric_key: SymmetricKey<T>,
-    filter: Filter,
-    public_key: Option<PublicKey>,
+    filter: Filter<BITS>,
+    sboxes: Sboxes<T, BITS>,
+    context: T::EvaluationContext,
+    max_nibbles_per_nonce: u64,
+    usage: Mutex<Usage>,
 }
 
 /// The struct used to encrypt, decrypt and transcrypt nibbles.
-impl<T: 'static + Nibble> Encrypter<T> {
+impl<T: 'static + Nibble<BITS>, const BITS: usize> Encrypter<T, BITS>
+where
+    Uint<BITS>: Width,
+{
     /// Generates to new encrypters, using possibly two different kind of nibbles T and U.
     /// One can be used to encrypted nibbles T and the other to transcrypt toward U.
-    pub fn new<U: Nibble>(
-        params: &SystemParameters,
-        secret_key: Option<&LweSecretKey<Vec<bool>>>,
-        std_dev: Option<f64>,
-        public_key: Option<PublicKey>,
-    ) -> (Self, Encrypter<U>) {
-        let mut rng = RandomGenerator::new(None);
-        let seed = rng.random_uniform();
-
-        let Parameters {
-            n,
-            key_size,
-            filter,
-        } = params.parameters();
-
-        let mut key1 =
-            vec![
-                T::from_u4_with_lwe_size(u4(0), secret_key.map(|s| s.key_size().to_lwe_size()));
-                key_size
-            ];
-        let mut key2 =
-            vec![
-                U::from_u4_with_lwe_size(u4(0), secret_key.map(|s| s.key_size().to_lwe_size()));
-                key_size
-            ];
-
-        Self::key_gen(&mut key1, &mut key2, secret_key, std_dev, &mut rng);
-
-        (
-            Self {
-                symmetric_key: SymmetricKey::new(key1, n, seed),
-                filter: filter.clone(),
-                public_key: public_key.clone(),
-            },
-            Encrypter::<U> {
-                symmetric_key: SymmetricKey::new(key2, n, seed),
-                filter,
-                public_key,
-            },
-        )
-    }
-
-    fn key_gen<U: Nibble>(
-        key1: &mut [T],
-        key2: &mut [U],
-        secret_key: Option<&LweSecretKey<Vec<bool>>>,
-        std_dev: Option<f64>,
-        rng: &mut RandomGenerator,
-    ) {
-        let env_var = env::var("KEY_DIRECTORY").ok();
-        let path = env_var.as_ref().map(|s| &**s);
-        let key_stored = path
-            .map(|p| Path::new(format!("{}/keys/symmetric", p).as_str()).is_dir())
-            .unwrap_or(false);
-
-        if key_stored {
-            let sk1_serialized =
-                fs::read(format!("{}/keys/symmetric/key_client", path.unwrap())).unwrap();
-            let sk2_serialized =
-                fs::read(format!("{}/keys/symmetric/key_server", path.unwrap())).unwrap();
-            key1.clone_from_slice(
-                bincode::deserialize::<Vec<T>>(&sk1_serialized)
-                    .unwrap()
-                    .as_slice(),
-            );
-            key2.clone_from_slice(
-                bincode::deserialize::<Vec<U>>(&sk2_serialized)
-                    .unwrap()
-                    .as_slice(),
-            );
-        } else {
-            for (k1, k2) in key1.iter_mut().zip(key2.iter_mut()) {
-                let u = u4(rng.random_uniform::<u8>() % (1 << 4));
-                *k1 = T::from_u4(u, secret_key, std_dev);
-                *k2 = U::from_u4(u, secret_key, std_dev);
+    /// Each one needs the material to encrypt its copy of the symmetric key, and to compute on it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MissingKey` or `CorruptKey` if `KEY_DIRECTORY` holds unreadable symmetric keys,
+    /// `Io` if they can not be written, or the error of the encryption of the key or of the
+    /// preparation of the S-boxes.
+    pub fn new<U: Nibble<BITS>>(
+        params: &impl ParameterSet<BITS>,
+        encryption: (&T::EncryptionContext, &U::EncryptionContext),
+        evaluation: (T::EvaluationContext, U::EvaluationContext),
+    ) -> Result<(Self, Encrypter<U, BITS>), ElisabethError> {
+        let parameters = params.parameters();
+        let (key1, key2) = Self::key_gen::<U>(encryption, &parameters)?;
+
+        Ok((
+            Self::with_key(key1, &parameters, evaluation.0)?,
+            Encrypter::with_key(key2, &parameters, evaluation.1)?,
+        ))
+    }
+
+    fn key_gen<U: Nibble<BITS>>(
+        (context1, context2): (&T::EncryptionContext, &U::EncryptionContext),
+        params: &Parameters<BITS>,
+    ) -> Result<(Vec<T>, Vec<U>), ElisabethError> {
+        let path = storage::key_directory().map(|p| p.join("symmetric"));
+
+        let (key1, key2) = match &path {
+            Some(p) if p.is_dir() => (
+                storage::read_key(p.join("key_client"))?,
+                storage::read_key(p.join("key_server"))?,
+            ),
+            _ => {
+                let clear_key = Zeroizing::new(params.generate_symmetric_key());
+                (
+                    clear_key
+                        .iter()
+                        .map(|&u| T::encrypt(u, context1))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    clear_key
+                        .iter()
+                        .map(|&u| U::encrypt(u, context2))
+                        .collect::<Result<Vec<_>, _>>()?,
+                )
             }
+        };
+
+        if let Some(p) = path {
+            storage::write_key(p.join("key_client"), &key1)?;
+            storage::write_key(p.join("key_server"), &key2)?;
         }
-        if path.is_some() {
-            fs::create_dir_all(format!("{}/keys/symmetric", path.unwrap())).unwrap();
-            fs::write(
-                format!("{}/keys/symmetric/key_client", path.unwrap()),
-                &bincode::serialize(&key1.to_vec()).unwrap(),
-            )
-            .unwrap();
-            fs::write(
-                format!("{}/keys/symmetric/key_server", path.unwrap()),
-                bincode::serialize(&key2.to_vec()).unwrap(),
-            )
-            .unwrap();
+        Ok((key1, key2))
+    }
+
+    /// Computes the nibbles `offset..offset + len` of the keystream selected by `nonce`, with the
+    /// bitsliced filter if the nibbles support it.
+    fn keystream(&self, nonce: Nonce, offset: u64, len: usize) -> Result<Vec<T>, ElisabethError> {
+        T::bitsliced_keystream(&self.filter, &self.symmetric_key, nonce, offset, len)
+            .unwrap_or_else(|| self.scalar_keystream(nonce, offset, len))
+    }
+
+    /// Computes the nibbles `offset..offset + len` of the keystream selected by `nonce`.
+    #[cfg(not(feature = "multithread"))]
+    fn scalar_keystream(
+        &self,
+        nonce: Nonce,
+        offset: u64,
+        len: usize,
+    ) -> Result<Vec<T>, ElisabethError> {
+        let n = self.symmetric_key.round_size();
+        (offset..offset + len as u64)
+            .map(|counter| {
+                let round = self.symmetric_key.round(nonce, counter)?;
+                let key_round = self.symmetric_key.whitened_subset(&round, 0..n);
+                self.filter.call(&key_round, &self.sboxes, &self.context)
+            })
+            .collect()
+    }
+
+    /// Computes the nibbles `offset..offset + len` of the keystream selected by `nonce`.
+    ///
+    /// The public randomness of every position is drawn first. Then, the blocks of the filter of
+    /// all the positions of a window are evaluated as independent tasks, so that every thread is
+    /// kept busy regardless of the width of the filter, while bounding the number of block outputs
+    /// held in memory at once.
+    #[cfg(feature = "multithread")]
+    fn scalar_keystream(
+        &self,
+        nonce: Nonce,
+        offset: u64,
+        len: usize,
+    ) -> Result<Vec<T>, ElisabethError> {
+        let rounds = (offset..offset + len as u64)
+            .into_par_iter()
+            .map(|counter| self.symmetric_key.round(nonce, counter))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let block_width = self.filter.block_width();
+        let blocks = self.symmetric_key.round_size() / block_width;
+        let window = rayon::current_num_threads();
+
+        let mut res = Vec::with_capacity(len);
+        for rounds in rounds.chunks(window) {
+            let block_outputs = (0..rounds.len() * blocks)
+                .into_par_iter()
+                .map(|task| {
+                    let block = task % blocks;
+                    let key_block = self.symmetric_key.whitened_subset(
+                        &rounds[task / blocks],
+                        block * block_width..(block + 1) * block_width,
+                    );
+                    self.filter
+                        .filter_block(&key_block, &self.sboxes, &self.context)
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(Zeroizing::new)?;
+
+            res.par_extend(block_outputs.par_chunks(blocks).map(|outputs| {
+                outputs[1..]
+                    .iter()
+                    .fold(outputs[0].clone(), |acc, output| acc.add(output))
+            }));
         }
+        Ok(res)
     }
 
-    fn stream(&mut self) -> T {
-        let key_round = self.symmetric_key.random_whitened_subset();
-        self.filter.call(&key_round, self.public_key.as_ref())
+    /// Encrypts the given vector of plaintexts under the keystream selected by `nonce`.
+    /// The nonce must be sent along with the ciphertext, and never be reused for another message.
+    ///
+    /// # Errors
+    ///
+    /// Fails as `encrypt_at`.
+    pub fn encrypt(
+        &self,
+        nonce: Nonce,
+        res: &mut [T],
+        message: &[Uint<BITS>],
+    ) -> Result<(), ElisabethError> {
+        self.encrypt_at(nonce, 0, res, message)
     }
 
-    /// Encrypts the given vector of plaintexts.
-    pub fn encrypt(&mut self, res: &mut [T], message: &[u4]) {
-        for (c, m) in res.iter_mut().zip(message.iter()) {
-            *c = self.stream();
-            c.add_assign_u4(m);
+    /// Encrypts the given vector of plaintexts with the keystream selected by `nonce`, starting at
+    /// its `offset`-th nibble.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LengthMismatch` if `res` and `message` differ in length, `NonceLimit` if the
+    /// message goes beyond the keystream of a nonce, `KeyLimit` if it would exceed the data
+    /// allowed under the key, `Io` if the usage of the key can not be saved, or the error of an
+    /// operation on the nibbles.
+    pub fn encrypt_at(
+        &self,
+        nonce: Nonce,
+        offset: u64,
+        res: &mut [T],
+        message: &[Uint<BITS>],
+    ) -> Result<(), ElisabethError> {
+        check_length(res.len(), message.len())?;
+        self.check_nonce_range(offset, message.len())?;
+        self.usage().consume(message.len() as u64)?;
+        // the keystream is derived from the key, and is wiped once used
+        let keystream = Zeroizing::new(self.keystream(nonce, offset, message.len())?);
+        for (c, (k, m)) in res.iter_mut().zip(keystream.iter().zip(message.iter())) {
+            *c = k.add_u4(m);
         }
+        Ok(())
     }
 
     /// Decrypts the given vector of ciphertexts.
     /// Depending of the type of nibbles set at the generation of the encrypter, this function can either decrypt or transcrypt.
-    pub fn decrypt(&mut self, res: &mut [T], ciphertext: &[u4]) {
+    /// `nonce` must be the one that was used to encrypt the ciphertext.
+    ///
+    /// # Errors
+    ///
+    /// Fails as `decrypt_at`.
+    pub fn decrypt(
+        &self,
+        nonce: Nonce,
+        res: &mut [T],
+        ciphertext: &[Uint<BITS>],
+    ) -> Result<(), ElisabethError> {
+        self.decrypt_at(nonce, 0, res, ciphertext)
+    }
+
+    /// Decrypts the given vector of ciphertexts, assuming its first nibble is the `offset`-th one
+    /// of the message encrypted under `nonce`.
+    /// This allows to decrypt or transcrypt any range of a message without processing what precedes it.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LengthMismatch` if `res` and `ciphertext` differ in length, `NonceLimit` if the
+    /// ciphertext goes beyond the keystream of a nonce, or the error of an operation on the
+    /// nibbles.
+    pub fn decrypt_at(
+        &self,
+        nonce: Nonce,
+        offset: u64,
+        res: &mut [T],
+        ciphertext: &[Uint<BITS>],
+    ) -> Result<(), ElisabethError> {
+        check_length(res.len(), ciphertext.len())?;
+        for (d, decrypted) in res
+            .iter_mut()
+            .zip(self.decrypt_to_vec(nonce, offset, ciphertext)?)
+        {
+            *d = decrypted;
+        }
+        Ok(())
+    }
+
+    /// Same as `decrypt_at`, but allocates the output.
+    pub(crate) fn decrypt_to_vec(
+        &self,
+        nonce: Nonce,
+        offset: u64,
+        ciphertext: &[Uint<BITS>],
+    ) -> Result<Vec<T>, ElisabethError> {
+        self.check_nonce_range(offset, ciphertext.len())?;
+        let mut res = self.keystream(nonce, offset, ciphertext.len())?;
         for (d, c) in res.iter_mut().zip(ciphertext.iter()) {
-            *d = self.stream();
             d.negate();
             d.add_assign_u4(c);
         }
+        Ok(res)
+    }
+
+    /// Returns the number of nibbles that can still be encrypted under the symmetric key.
+    /// Once it reaches zero, `encrypt` fails and a new key must be used.
+    pub fn remaining_nibbles(&self) -> u64 {
+        self.usage().remaining()
+    }
+
+    /// Lowers the number of nibbles that can be encrypted under the symmetric key below the one
+    /// set by the parameters. The limit can not be raised.
+    pub fn limit_nibbles(&mut self, max_nibbles: u64) {
+        self.usage().restrict(max_nibbles);
+    }
+
+    fn usage(&self) -> MutexGuard<'_, Usage> {
+        // the count is only updated once persisted, so it stays consistent after a panic
+        self.usage.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn check_nonce_range(&self, offset: u64, len: usize) -> Result<(), ElisabethError> {
+        match offset.checked_add(len as u64) {
+            Some(end) if end <= self.max_nibbles_per_nonce => Ok(()),
+            end => Err(ElisabethError::NonceLimit {
+                limit: self.max_nibbles_per_nonce,
+                end: end.unwrap_or(u64::MAX),
+            }),
+        }
+    }
+}
+
+impl<T: Nibble<BITS>, const BITS: usize> Encrypter<T, BITS>
+where
+    Uint<BITS>: Width,
+{
+    fn with_key(
+        key: Vec<T>,
+        parameters: &Parameters<BITS>,
+        context: T::EvaluationContext,
+    ) -> Result<Self, ElisabethError> {
+        check_key_size(key.len(), parameters.key_size)?;
+        let usage = if T::CLEAR {
+            Usage::load(&key, parameters.max_nibbles_per_key)?
+        } else {
+            Usage::in_memory(parameters.max_nibbles_per_key)
+        };
+        let mut symmetric_key = SymmetricKey::new(key, parameters.n, parameters.xof)?;
+        if T::CLEAR {
+            symmetric_key.lock();
+        }
+
+        Ok(Self {
+            usage: Mutex::new(usage),
+            symmetric_key,
+            filter: parameters.filter.clone(),
+            sboxes: parameters.filter.sboxes(&context)?,
+            context,
+            max_nibbles_per_nonce: parameters.max_nibbles_per_nonce,
+        })
+    }
+}
+
+impl<const BITS: usize> Encrypter<Uint<BITS>, BITS>
+where
+    Uint<BITS>: Width,
+{
+    /// Builds the client side encrypter from a clear symmetric key, as generated by
+    /// `SystemParameters::generate_symmetric_key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParameterMismatch` if the key does not have the size set by the parameters, or
+    /// the error of loading the usage of the key from `KEY_DIRECTORY`.
+    pub fn from_symmetric_key(
+        params: &impl ParameterSet<BITS>,
+        key: Vec<Uint<BITS>>,
+    ) -> Result<Self, ElisabethError> {
+        Self::with_key(key, &params.parameters(), ())
+    }
+
+    /// Replaces the symmetric key by a fresh one, for instance once the current one is exhausted.
+    /// The new key starts with the limit set by the parameters, and must be exported again for
+    /// the server to build a new `Transcipherer`.
+    ///
+    /// When `KEY_DIRECTORY` is set, the new key replaces `keys/symmetric/key_client` before it is
+    /// used, so that a restarted client reloads it rather than the exhausted one. The encrypted
+    /// copy of the previous key, `keys/symmetric/key_server`, is removed, as it no longer matches.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Io` or `Serialization` if the new key can not be saved, in which case the current
+    /// key is kept, or fails as `from_symmetric_key`.
+    pub fn rekey(&mut self, params: &impl ParameterSet<BITS>) -> Result<(), ElisabethError> {
+        let key = params.parameters().generate_symmetric_key();
+        if let Some(p) = storage::key_directory().map(|p| p.join("symmetric")) {
+            storage::write_key(p.join("key_client"), &key)?;
+            storage::remove_key(p.join("key_server"))?;
+        }
+        *self = Self::from_symmetric_key(params, key)?;
+        Ok(())
+    }
+
+    /// Encrypts the symmetric key of this encrypter with the encryption key of an FHE backend, such
+    /// as the LWE secret key and standard deviation output by `SystemParameters::generate_fhe_keys`.
+    /// The result is meant to be sent to the server, along with the public key, to build a
+    /// `Transcipherer` able to transcrypt the ciphertexts of this encrypter.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a nibble of the key can not be encrypted.
+    pub fn export_encrypted_key<K: BackendKey>(
+        &self,
+        secret_key: &K,
+    ) -> Result<Vec<LWE<K::Backend, BITS>>, ElisabethError>
+    where
+        K::Backend: FheBackend<EncryptionKey = K>,
+    {
+        self.symmetric_key
+            .key()
+            .iter()
+            .map(|&u| LWE::encrypt(u, secret_key))
+            .collect()
+    }
+}
+
+impl<B: FheBackend, const BITS: usize> Encrypter<LWE<B, BITS>, BITS>
+where
+    Uint<BITS>: Width,
+{
+    /// Builds the server side encrypter from the encrypted symmetric key exported by the client,
+    /// and the public key matching the secret key it was encrypted under. `Transcipherer::new`
+    /// wraps it into a type that only exposes transciphering.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParameterMismatch` if the key does not have the size set by the parameters, or if
+    /// a nibble of the key is not under the input key of the bootstrappings of `public_key`.
+    pub fn from_encrypted_key(
+        params: &impl ParameterSet<BITS>,
+        key: Vec<LWE<B, BITS>>,
+        public_key: B::PublicKey,
+    ) -> Result<Self, ElisabethError> {
+        for nibble in &key {
+            nibble.check_input(&public_key)?;
+        }
+
+        Self::with_key(key, &params.parameters(), public_key)
+    }
+}
+
+fn check_key_size(found: usize, expected: usize) -> Result<(), ElisabethError> {
+    if found == expected {
+        Ok(())
+    } else {
+        Err(ElisabethError::ParameterMismatch(format!(
+            "the symmetric key has {found} nibbles instead of {expected}"
+        )))
+    }
+}
+
+const fn check_length(found: usize, expected: usize) -> Result<(), ElisabethError> {
+    if found == expected {
+        Ok(())
+    } else {
+        Err(ElisabethError::LengthMismatch { expected, found })
     }
 }
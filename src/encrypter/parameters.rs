@@ -1,44 +1,269 @@
-use crate::{filter::Filter, public_key::PublicKey, Torus};
-use concrete_commons::{Numeric, StandardDev};
+use crate::{
+    backend::{Concrete, FheBackend, PublicKey},
+    error::ElisabethError,
+    filter::{Filter, FilterSpec},
+    nibble::{Uint, Width},
+    secret::FheSecretKey,
+    storage,
+    symmetric_key::{Nonce, Round},
+    u4,
+    xof::XofKind,
+};
+use concrete_commons::StandardDev;
 use concrete_core::{
-    crypto::{
-        bootstrap::BootstrapKey,
-        lwe::LweKeyswitchKey,
-        secret::{GlweSecretKey, LweSecretKey},
-        GlweDimension, LweDimension,
-    },
+    crypto::{GlweDimension, LweDimension},
     math::{
         decomposition::{DecompositionBaseLog, DecompositionLevelCount},
-        fft::Complex64,
         polynomial::PolynomialSize,
-        random::{EncryptionRandomGenerator, RandomGenerator},
+        random::RandomGenerator,
     },
 };
-use std::{env, fs, path::Path};
 
 /// A list of preset parameters for Elisabeth.
 #[allow(non_camel_case_types)]
 pub enum SystemParameters {
+    /// Elisabeth-4, drawing its public randomness with AES-128 in counter mode.
     n60,
+    /// Elisabeth-4, drawing its public randomness with SHAKE128.
+    n60_shake,
+    /// A user-defined filter on nibbles of 4 bits.
+    Custom(CustomParameters),
+}
+
+/// Parameters built around a custom filter, on nibbles of `BITS` bits.
+///
+/// They use the FHE parameters of `n60`, which were only chosen for the noise of its filter on 4
+/// bits. Nibbles of up to 4 bits can be transciphered, but wider ones leave too little room for the
+/// noise, so that encrypting their key with `Encrypter::export_encrypted_key` or building a
+/// `Transcipherer` for them returns `ParameterMismatch`. They can still be encrypted in the clear.
+#[derive(Clone)]
+pub struct CustomParameters<const BITS: usize = 4>
+where
+    Uint<BITS>: Width,
+{
+    n: usize,
+    key_size: usize,
+    filter: Filter<BITS>,
+    xof: XofKind,
+}
+
+impl<const BITS: usize> CustomParameters<BITS>
+where
+    Uint<BITS>: Width,
+{
+    /// Checks the given filter, and that a key round of `n` nibbles drawn from a key of
+    /// `key_size` nibbles can be split into blocks of the filter.
+    /// The key can have at most `2^16` nibbles, as the public randomness draws their indices from
+    /// two bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidFilter` if the filter fails `FilterSpec::validate`, and `ParameterMismatch`
+    /// if the key round does not fit the filter or the key.
+    pub fn new(
+        n: usize,
+        key_size: usize,
+        filter: FilterSpec<BITS>,
+        xof: XofKind,
+    ) -> Result<Self, ElisabethError> {
+        let filter = Filter::new(filter)?;
+        if n == 0 || !n.is_multiple_of(filter.block_width()) {
+            return Err(ElisabethError::ParameterMismatch(format!(
+                "a key round of {} nibbles can not be split into blocks of {}",
+                n,
+                filter.block_width()
+            )));
+        }
+        if n > key_size {
+            return Err(ElisabethError::ParameterMismatch(format!(
+                "a key round of {n} nibbles can not be drawn from a key of {key_size}"
+            )));
+        }
+        if key_size > MAX_KEY_SIZE {
+            return Err(ElisabethError::ParameterMismatch(format!(
+                "a key of {key_size} nibbles is larger than the limit of {MAX_KEY_SIZE}"
+            )));
+        }
+        Ok(Self {
+            n,
+            key_size,
+            filter,
+            xof,
+        })
+    }
+
+    /// Draws a new clear symmetric key.
+    #[must_use]
+    pub fn generate_symmetric_key(&self) -> Vec<Uint<BITS>> {
+        sealed::Sealed::parameters(self).generate_symmetric_key()
+    }
+
+    /// Returns the public randomness of the `counter`-th nibble of the keystream selected by
+    /// `nonce`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NonceLimit` if `counter` is beyond the keystream of a nonce.
+    pub fn round(&self, nonce: Nonce, counter: u64) -> Result<Round<BITS>, ElisabethError> {
+        Round::new(self.xof, nonce, counter, self.n, self.key_size)
+    }
+}
+
+/// Parameters an `Encrypter` on nibbles of `BITS` bits can be built from: the presets of
+/// `SystemParameters`, on 4 bits, or `CustomParameters` of any width.
+///
+/// The trait is sealed, so that the parameters it gives access to stay internal to the crate.
+pub trait ParameterSet<const BITS: usize = 4>: sealed::Sealed<BITS>
+where
+    Uint<BITS>: Width,
+{
+}
+
+impl ParameterSet for SystemParameters {}
+
+impl<const BITS: usize> ParameterSet<BITS> for CustomParameters<BITS> where Uint<BITS>: Width {}
+
+// `Parameters` is internal to the crate, and so is `Sealed`, although a bound on `ParameterSet`
+// lets the compiler reach it
+#[allow(private_interfaces)]
+mod sealed {
+    use super::{
+        CustomParameters, Parameters, SystemParameters, Uint, Width, MAX_NIBBLES_PER_KEY,
+        MAX_NIBBLES_PER_NONCE,
+    };
+
+    /// The supertrait of `ParameterSet`, which can not be named outside of the crate.
+    pub trait Sealed<const BITS: usize>
+    where
+        Uint<BITS>: Width,
+    {
+        fn parameters(&self) -> Parameters<BITS>;
+    }
+
+    impl Sealed<4> for SystemParameters {
+        fn parameters(&self) -> Parameters {
+            // the inherent method, available within the crate without importing the trait
+            Self::parameters(self)
+        }
+    }
+
+    impl<const BITS: usize> Sealed<BITS> for CustomParameters<BITS>
+    where
+        Uint<BITS>: Width,
+    {
+        fn parameters(&self) -> Parameters<BITS> {
+            Parameters {
+                key_size: self.key_size,
+                n: self.n,
+                filter: self.filter.clone(),
+                xof: self.xof,
+                max_nibbles_per_nonce: MAX_NIBBLES_PER_NONCE,
+                max_nibbles_per_key: MAX_NIBBLES_PER_KEY,
+            }
+        }
+    }
+}
+
+// the module is private, but `pub(crate)` states that the parameters never leave the crate
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) struct Parameters<const BITS: usize = 4>
+where
+    Uint<BITS>: Width,
+{
+    pub(crate) n: usize,
+    pub(crate) key_size: usize,
+    pub(crate) filter: Filter<BITS>,
+    pub(super) xof: XofKind,
+    pub(super) max_nibbles_per_nonce: u64,
+    pub(super) max_nibbles_per_key: u64,
 }
-pub(super) struct Parameters {
-    pub(super) n: usize,
-    pub(super) key_size: usize,
-    pub(super) filter: Filter,
+
+impl<const BITS: usize> Parameters<BITS>
+where
+    Uint<BITS>: Width,
+{
+    pub(crate) fn generate_symmetric_key(&self) -> Vec<Uint<BITS>> {
+        let mut generator = RandomGenerator::new(None);
+        (0..self.key_size)
+            .map(|_| Uint(generator.random_uniform::<u8>() & Uint::<BITS>::MASK))
+            .collect()
+    }
 }
 
+/// The largest symmetric key, in nibbles, whose indices can be drawn from two bytes of the
+/// public randomness.
+const MAX_KEY_SIZE: usize = 1 << 16;
+/// The length of keystream allowed for a single nonce, whose nibbles are numbered by the 32-bit
+/// counter of the seed of the public randomness.
+///
+/// An attacker gains nothing from getting its keystream under one nonce rather than several, so the
+/// bound on the data it sees is `MAX_NIBBLES_PER_KEY`. This one is 2^8 times lower, so that a key
+/// always serves at least 256 messages of the largest size, that is 2 GiB of plaintext each.
+const MAX_NIBBLES_PER_NONCE: u64 = 1 << 32;
+/// The number of nibbles that can be encrypted under a single symmetric key, over all nonces.
+///
+/// This is a policy limit of this crate, that is 512 GiB of plaintext per key. It is not derived
+/// from the cryptanalysis of Elisabeth-4, which gives no data bound it could be taken from, and it
+/// does not make up for the attacks that led its authors to revise it. Applications that need a
+/// tighter bound can lower it with `Encrypter::limit_nibbles`.
+const MAX_NIBBLES_PER_KEY: u64 = 1 << 40;
+
 impl SystemParameters {
     /// Returns the parameters for a given preset.
-    pub(super) fn parameters(&self) -> Parameters {
+    pub(crate) fn parameters(&self) -> Parameters {
         match self {
             Self::n60 => Parameters {
                 key_size: 256,
                 n: 60,
-                filter: Filter::new(),
+                filter: Filter::elisabeth4(),
+                xof: XofKind::AesCtr,
+                max_nibbles_per_nonce: MAX_NIBBLES_PER_NONCE,
+                max_nibbles_per_key: MAX_NIBBLES_PER_KEY,
+            },
+            Self::n60_shake => Parameters {
+                key_size: 256,
+                n: 60,
+                filter: Filter::elisabeth4(),
+                xof: XofKind::Shake128,
+                max_nibbles_per_nonce: MAX_NIBBLES_PER_NONCE,
+                max_nibbles_per_key: MAX_NIBBLES_PER_KEY,
             },
+            Self::Custom(custom) => sealed::Sealed::parameters(custom),
         }
     }
 
+    /// Draws a new clear symmetric key for this preset.
+    /// It is wiped from memory once the encrypter built from it is dropped.
+    #[must_use]
+    pub fn generate_symmetric_key(&self) -> Vec<u4> {
+        self.parameters().generate_symmetric_key()
+    }
+
+    /// Returns the public randomness of the `counter`-th nibble of the keystream selected by
+    /// `nonce`, that is the indices of the selected key nibbles and their whitening.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NonceLimit` if `counter` is beyond the keystream of a nonce.
+    pub fn round(&self, nonce: Nonce, counter: u64) -> Result<Round, ElisabethError> {
+        let Parameters {
+            n, key_size, xof, ..
+        } = self.parameters();
+        Round::new(xof, nonce, counter, n, key_size)
+    }
+
+    /// Returns the number of keystream nibbles that can be used under a single nonce.
+    #[must_use]
+    pub fn max_nibbles_per_nonce(&self) -> u64 {
+        self.parameters().max_nibbles_per_nonce
+    }
+
+    /// Returns the number of nibbles that can be encrypted under a single symmetric key.
+    #[must_use]
+    pub fn max_nibbles_per_key(&self) -> u64 {
+        self.parameters().max_nibbles_per_key
+    }
+
     #[cfg(not(feature = "single_key"))]
     pub fn fhe_parameters(
         &self,
@@ -98,217 +323,52 @@ impl SystemParameters {
         )
     }
 
-    #[cfg(not(feature = "single_key"))]
-    pub fn generate_fhe_keys(
-        &self,
-    ) -> (
-        (LweSecretKey<Vec<bool>>, StandardDev),
-        LweSecretKey<Vec<bool>>,
-        PublicKey,
-    ) {
-        let env_var = env::var("KEY_DIRECTORY").ok();
-        let path = env_var.as_ref().map(|s| &**s);
-
-        let key_stored = path
-            .map(|p| Path::new(format!("{}/keys", p).as_str()).is_dir())
-            .unwrap_or(false);
-
-        if key_stored {
-            let sk_serialized =
-                fs::read(format!("{}/keys/secret/secret_key", path.unwrap())).unwrap();
-            let std_dev_serialized =
-                fs::read(format!("{}/keys/secret/standard_deviation", path.unwrap())).unwrap();
-            let sk_out_serialized =
-                fs::read(format!("{}/keys/secret/secret_key_out", path.unwrap())).unwrap();
-            let pk_serialized =
-                fs::read(format!("{}/keys/public/public_key", path.unwrap())).unwrap();
+    /// Generates the secret keys and the public key of the FHE backend `B` for this preset, or
+    /// reads them from `KEY_DIRECTORY` if it exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MissingKey` or `CorruptKey` if a key of `KEY_DIRECTORY` can not be read, `Io` or
+    /// `Serialization` if the new keys can not be written there, or the error of their generation.
+    pub fn generate_keys<B: FheBackend>(&self) -> Result<B::Keys, ElisabethError> {
+        let path = storage::key_directory();
 
-            (
-                (
-                    bincode::deserialize(&sk_serialized).unwrap(),
-                    bincode::deserialize(&std_dev_serialized).unwrap(),
-                ),
-                bincode::deserialize(&sk_out_serialized).unwrap(),
-                bincode::deserialize(&pk_serialized).unwrap(),
-            )
+        if let Some(p) = path.as_ref().filter(|p| p.is_dir()) {
+            B::read_keys(p)
         } else {
-            //parameters
-            let (
-                (lwe_dimension, std_dev_lwe),
-                (glwe_dimension, polynomial_size, std_dev_glwe),
-                (base_log_bs, level_bs),
-                (base_log_ks, level_ks),
-                (base_log_ks_inv, level_ks_inv),
-            ) = self.fhe_parameters();
-
-            // secret keys
-            let mut generator = RandomGenerator::new(None);
-            let mut secret_generator = EncryptionRandomGenerator::new(None);
-            let sk_rlwe = GlweSecretKey::generate(glwe_dimension, polynomial_size, &mut generator);
-            let sk = LweSecretKey::generate(lwe_dimension, &mut generator);
-
-            // bootstrapping key
-            let mut coef_bsk = BootstrapKey::allocate(
-                <Torus as Numeric>::ZERO,
-                glwe_dimension.to_glwe_size(),
-                polynomial_size,
-                level_bs,
-                base_log_bs,
-                lwe_dimension,
-            );
-            coef_bsk.fill_with_new_key(&sk, &sk_rlwe, std_dev_glwe, &mut secret_generator);
-            let mut bsk = BootstrapKey::allocate(
-                Complex64::new(0., 0.),
-                glwe_dimension.to_glwe_size(),
-                polynomial_size,
-                level_bs,
-                base_log_bs,
-                lwe_dimension,
-            );
-            bsk.fill_with_forward_fourier(&coef_bsk);
-
-            let sk_out = sk_rlwe.into_lwe_secret_key();
-            let mut ksk = LweKeyswitchKey::allocate(
-                <Torus as Numeric>::ZERO,
-                level_ks,
-                base_log_ks,
-                sk_out.key_size(),
-                sk.key_size(),
-            );
-            ksk.fill_with_keyswitch_key(&sk_out, &sk, std_dev_lwe, &mut secret_generator);
-
-            let mut ksk_inv = LweKeyswitchKey::allocate(
-                <Torus as Numeric>::ZERO,
-                level_ks_inv,
-                base_log_ks_inv,
-                sk.key_size(),
-                sk_out.key_size(),
-            );
-            ksk_inv.fill_with_keyswitch_key(&sk, &sk_out, std_dev_glwe, &mut secret_generator);
-
-            let pk = PublicKey { bsk, ksk, ksk_inv };
-
-            if path.is_some() {
-                fs::create_dir_all(format!("{}/keys/secret", path.unwrap())).unwrap();
-                fs::create_dir_all(format!("{}/keys/public", path.unwrap())).unwrap();
-                fs::write(
-                    format!("{}/keys/secret/secret_key", path.unwrap()),
-                    &bincode::serialize(&sk).unwrap(),
-                )
-                .unwrap();
-                fs::write(
-                    format!("{}/keys/secret/standard_deviation", path.unwrap()),
-                    bincode::serialize(&std_dev_lwe).unwrap(),
-                )
-                .unwrap();
-                fs::write(
-                    format!("{}/keys/secret/secret_key_out", path.unwrap()),
-                    &bincode::serialize(&sk_out).unwrap(),
-                )
-                .unwrap();
-                fs::write(
-                    format!("{}/keys/public/public_key", path.unwrap()),
-                    bincode::serialize(&pk).unwrap(),
-                )
-                .unwrap();
+            let keys = B::generate_keys(self)?;
+            if let Some(p) = path {
+                B::write_keys(&p, &keys)?;
             }
-
-            ((sk, std_dev_lwe), sk_out, pk)
+            Ok(keys)
         }
     }
 
-    #[cfg(feature = "single_key")]
-    pub fn generate_fhe_keys(&self) -> ((LweSecretKey<Vec<bool>>, StandardDev), PublicKey) {
-        let env_var = env::var("KEY_DIRECTORY").ok();
-        let path = env_var.as_ref().map(|s| &**s);
-
-        let key_stored = path
-            .map(|p| Path::new(format!("{}/keys", p).as_str()).is_dir())
-            .unwrap_or(false);
-
-        if key_stored {
-            let sk_serialized =
-                fs::read(format!("{}/keys/secret/secret_key", path.unwrap())).unwrap();
-            let std_dev_serialized =
-                fs::read(format!("{}/keys/secret/standard_deviation", path.unwrap())).unwrap();
-            let pk_serialized =
-                fs::read(format!("{}/keys/public/public_key", path.unwrap())).unwrap();
-
-            (
-                (
-                    bincode::deserialize(&sk_serialized).unwrap(),
-                    bincode::deserialize(&std_dev_serialized).unwrap(),
-                ),
-                bincode::deserialize(&pk_serialized).unwrap(),
-            )
-        } else {
-            //parameters
-            let (
-                (lwe_dimension, std_dev_lwe),
-                (glwe_dimension, polynomial_size, std_dev_glwe),
-                (base_log_bs, level_bs),
-                (base_log_ks, level_ks),
-            ) = self.fhe_parameters();
-
-            // secret keys
-            let mut generator = RandomGenerator::new(None);
-            let mut secret_generator = EncryptionRandomGenerator::new(None);
-            let sk_rlwe = GlweSecretKey::generate(glwe_dimension, polynomial_size, &mut generator);
-            let sk = LweSecretKey::generate(lwe_dimension, &mut generator);
-
-            // bootstrapping key
-            let mut coef_bsk = BootstrapKey::allocate(
-                <Torus as Numeric>::ZERO,
-                glwe_dimension.to_glwe_size(),
-                polynomial_size,
-                level_bs,
-                base_log_bs,
-                lwe_dimension,
-            );
-            coef_bsk.fill_with_new_key(&sk, &sk_rlwe, std_dev_glwe, &mut secret_generator);
-            let mut bsk = BootstrapKey::allocate(
-                Complex64::new(0., 0.),
-                glwe_dimension.to_glwe_size(),
-                polynomial_size,
-                level_bs,
-                base_log_bs,
-                lwe_dimension,
-            );
-            bsk.fill_with_forward_fourier(&coef_bsk);
-
-            let sk_out = sk_rlwe.into_lwe_secret_key();
-            let mut ksk = LweKeyswitchKey::allocate(
-                <Torus as Numeric>::ZERO,
-                level_ks,
-                base_log_ks,
-                sk_out.key_size(),
-                sk.key_size(),
-            );
-            ksk.fill_with_keyswitch_key(&sk_out, &sk, std_dev_lwe, &mut secret_generator);
-
-            let pk = PublicKey { bsk, ksk };
-
-            if path.is_some() {
-                fs::create_dir_all(format!("{}/keys/secret", path.unwrap())).unwrap();
-                fs::create_dir_all(format!("{}/keys/public", path.unwrap())).unwrap();
-                fs::write(
-                    format!("{}/keys/secret/secret_key", path.unwrap()),
-                    &bincode::serialize(&sk).unwrap(),
-                )
-                .unwrap();
-                fs::write(
-                    format!("{}/keys/secret/standard_deviation", path.unwrap()),
-                    bincode::serialize(&std_dev_lwe).unwrap(),
-                )
-                .unwrap();
-                fs::write(
-                    format!("{}/keys/public/public_key", path.unwrap()),
-                    bincode::serialize(&pk).unwrap(),
-                )
-                .unwrap();
-            }
+    /// Generates the LWE secret key of the encrypted symmetric key, with the standard deviation
+    /// of its noise, the output key of the bootstrappings and the public key, or reads them from
+    /// `KEY_DIRECTORY` if it exists. This is `generate_keys` with the `Concrete` backend.
+    ///
+    /// # Errors
+    ///
+    /// Fails as `generate_keys`.
+    #[cfg(not(feature = "single_key"))]
+    pub fn generate_fhe_keys(
+        &self,
+    ) -> Result<((FheSecretKey, StandardDev), FheSecretKey, PublicKey), ElisabethError> {
+        self.generate_keys::<Concrete>()
+    }
 
-            ((sk, std_dev_lwe), pk)
-        }
+    /// Generates the LWE secret key of the encrypted symmetric key, with the standard deviation
+    /// of its noise, and the public key, or reads them from `KEY_DIRECTORY` if it exists. This is
+    /// `generate_keys` with the `Concrete` backend.
+    ///
+    /// # Errors
+    ///
+    /// Fails as `generate_keys`.
+    #[cfg(feature = "single_key")]
+    pub fn generate_fhe_keys(
+        &self,
+    ) -> Result<((FheSecretKey, StandardDev), PublicKey), ElisabethError> {
+        self.generate_keys::<Concrete>()
     }
 }
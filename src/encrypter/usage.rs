@@ -0,0 +1,84 @@
+use crate::{error::ElisabethError, storage};
+use serde::Serialize;
+use sha3::{Digest, Sha3_256};
+use std::{fmt::Write, path::PathBuf};
+
+/// Counts the keystream nibbles used for encryption under a symmetric key.
+///
+/// For a clear key, and when the `KEY_DIRECTORY` variable is set, the count is persisted in
+/// `symmetric/usage/<fingerprint>`, where the fingerprint is the SHA3-256 hash of the serialized
+/// key. The count is written before the keystream is produced, so that a client restarted after a
+/// crash never encrypts more than the limit. Encrypted keys are only held by the server, which
+/// transciphers but does not encrypt, so their count is kept in memory.
+pub struct Usage {
+    path: Option<PathBuf>,
+    used: u64,
+    limit: u64,
+}
+
+impl Usage {
+    /// Loads the count of the given key, or starts a new one if it was never used.
+    pub fn load<K: Serialize>(key: &K, limit: u64) -> Result<Self, ElisabethError> {
+        let path = storage::key_directory()
+            .map(|p| fingerprint(key).map(|f| p.join("symmetric/usage").join(f)))
+            .transpose()?;
+
+        let used = match &path {
+            Some(p) => match storage::read_key(p.clone()) {
+                Err(ElisabethError::MissingKey(_)) => 0,
+                used => used?,
+            },
+            None => 0,
+        };
+        Ok(Self { path, used, limit })
+    }
+
+    /// Starts a count that is kept in memory only.
+    pub const fn in_memory(limit: u64) -> Self {
+        Self {
+            path: None,
+            used: 0,
+            limit,
+        }
+    }
+
+    /// Lowers the number of nibbles that can be encrypted under the key.
+    pub fn restrict(&mut self, limit: u64) {
+        self.limit = self.limit.min(limit);
+    }
+
+    /// Returns the number of nibbles that can still be encrypted under the key.
+    pub const fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.used)
+    }
+
+    /// Accounts for `len` more nibbles, or fails without consuming anything if this would exceed
+    /// the limit.
+    pub fn consume(&mut self, len: u64) -> Result<(), ElisabethError> {
+        let used = self
+            .used
+            .checked_add(len)
+            .filter(|&used| used <= self.limit)
+            .ok_or(ElisabethError::KeyLimit {
+                limit: self.limit,
+                used: self.used,
+            })?;
+
+        if let Some(p) = &self.path {
+            storage::write_key(p.clone(), &used)?;
+        }
+        self.used = used;
+        Ok(())
+    }
+}
+
+fn fingerprint<K: Serialize>(key: &K) -> Result<String, ElisabethError> {
+    let serialized =
+        zeroize::Zeroizing::new(bincode::serialize(key).map_err(ElisabethError::Serialization)?);
+    Ok(Sha3_256::digest(&*serialized)
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        }))
+}
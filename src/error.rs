@@ -0,0 +1,78 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+/// The errors that can be returned by Elisabeth.
+#[derive(Debug)]
+pub enum ElisabethError {
+    /// A key file was expected in the key directory but does not exist.
+    MissingKey(PathBuf),
+    /// A key file exists but could not be deserialized, for instance because it was generated
+    /// with other parameters or in another keyswitching mode.
+    CorruptKey {
+        path: PathBuf,
+        source: bincode::Error,
+    },
+    /// A key could not be serialized.
+    Serialization(bincode::Error),
+    /// Reading or writing the key directory failed.
+    Io(io::Error),
+    /// A key or a ciphertext does not match the parameters it is used with.
+    ParameterMismatch(String),
+    /// An output buffer does not have the same length as the input.
+    LengthMismatch { expected: usize, found: usize },
+    /// A filter description can not be evaluated.
+    InvalidFilter(String),
+    /// A circuit refers to a node, an input or an S-box it does not have, or adds a value that is
+    /// not a nibble.
+    InvalidCircuit(String),
+    /// The requested nibbles go beyond the length of keystream allowed for a single nonce.
+    NonceLimit { limit: u64, end: u64 },
+    /// Encrypting the message would exceed the number of nibbles allowed under the symmetric key,
+    /// of which `used` were already consumed. A new key must be generated.
+    KeyLimit { limit: u64, used: u64 },
+}
+
+impl fmt::Display for ElisabethError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingKey(path) => write!(f, "missing key file {}", path.display()),
+            Self::CorruptKey { path, source } => {
+                write!(f, "corrupt key file {}: {source}", path.display())
+            }
+            Self::Serialization(error) => write!(f, "could not serialize key: {error}"),
+            Self::Io(error) => write!(f, "key directory error: {error}"),
+            Self::ParameterMismatch(reason) => write!(f, "parameter mismatch: {reason}"),
+            Self::LengthMismatch { expected, found } => write!(
+                f,
+                "length mismatch: expected {expected} nibbles, found {found}"
+            ),
+            Self::InvalidFilter(reason) => write!(f, "invalid filter: {reason}"),
+            Self::InvalidCircuit(reason) => write!(f, "invalid circuit: {reason}"),
+            Self::NonceLimit { limit, end } => write!(
+                f,
+                "nonce limit exceeded: nibbles up to {end} were requested, \
+                 but a nonce only allows {limit}"
+            ),
+            Self::KeyLimit { limit, used } => write!(
+                f,
+                "key limit exceeded: {used} of the {limit} nibbles allowed under this key \
+                 were already used"
+            ),
+        }
+    }
+}
+
+impl Error for ElisabethError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::CorruptKey { source, .. } | Self::Serialization(source) => Some(source),
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ElisabethError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
@@ -1,22 +1,40 @@
-use crate::{nibble::Nibble, public_key::PublicKey, u4};
-#[cfg(feature = "multithread")]
-use rayon::{
-    iter::{IntoParallelIterator, ParallelIterator},
-    slice::ParallelSlice,
+use crate::{
+    error::ElisabethError,
+    nibble::{Nibble, Uint, Width},
+    u4,
 };
+use zeroize::Zeroizing;
 
-#[derive(Clone)]
-pub struct Filter {
-    sbox: Vec<[u4; 16]>,
-    block_width: usize,
+/// The description of the filter of Elisabeth, from which custom parameters can be built.
+///
+/// The filter processes the key round by blocks of `block_width` nibbles `x`, with
+/// `block_width - 1` lanes each. The lane `i` computes
+/// `y[i] = first_layer[i](x[i] + x[first_wiring[i]])`, then
+/// `z[i] = second_layer[i](x[i] + y[j] + ...)` for all `j` in `second_wiring[i]`.
+/// The output of a block is the sum of the `z[i]` and of its last nibble, and the output of the
+/// filter is the sum of the outputs of all its blocks.
+///
+/// The nibbles, and thus the S-boxes, have `BITS` bits. Elisabeth-4 uses 4, but filters on 2 to 6
+/// bits can be described for research purposes.
+#[derive(Clone, Debug)]
+pub struct FilterSpec<const BITS: usize = 4>
+where
+    Uint<BITS>: Width,
+{
+    pub block_width: usize,
+    pub first_layer: Vec<<Uint<BITS> as Width>::Table>,
+    pub second_layer: Vec<<Uint<BITS> as Width>::Table>,
+    pub first_wiring: Vec<usize>,
+    pub second_wiring: Vec<Vec<usize>>,
 }
 
-/// An algorithm used to generate a random nibble from the secret key.
-impl Filter {
-    /// Generate a new filter.
-    pub fn new() -> Self {
+impl FilterSpec {
+    /// Returns the filter of Elisabeth-4.
+    #[must_use]
+    pub fn elisabeth4() -> Self {
         Self {
-            sbox: vec![
+            block_width: 5,
+            first_layer: vec![
                 [
                     u4(0x3),
                     u4(0x2),
@@ -89,6 +107,8 @@ impl Filter {
                     u4(0x4),
                     u4(0xB),
                 ],
+            ],
+            second_layer: vec![
                 [
                     u4(0x3),
                     u4(0x0),
@@ -162,103 +182,229 @@ impl Filter {
                     u4(0xF),
                 ],
             ],
-            block_width: 5,
+            first_wiring: (0..4).map(|i| (i + 1) % 4).collect(),
+            second_wiring: (0..4).map(|i| vec![(i + 1) % 4, (i + 2) % 4]).collect(),
         }
     }
+}
+
+impl<const BITS: usize> FilterSpec<BITS>
+where
+    Uint<BITS>: Width,
+{
+    /// Checks that the filter can be evaluated, that is that every lane has its S-boxes and
+    /// wiring, that the wiring only refers to existing lanes, and that every S-box is negacyclic.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidFilter`, with the first problem found.
+    pub fn validate(&self) -> Result<(), ElisabethError> {
+        let invalid = |reason: String| Err(ElisabethError::InvalidFilter(reason));
+        if self.block_width < 2 {
+            return invalid(format!(
+                "a block has {} nibbles, while at least 2 are needed",
+                self.block_width
+            ));
+        }
 
-    /// Generate a random nibble from keyround and stores it into rop.
-    /// If the key is encrypted, this function needs a public key to work.
-    #[cfg(feature = "multithread")]
-    pub fn call<T: Nibble>(&self, keyround: &[T], public_key: Option<&PublicKey>) -> T {
-        let lwe_size = public_key.map(|pk| {
-            if cfg!(feature = "single_key") {
-                pk.ksk.after_key_size().to_lwe_size()
-            } else {
-                pk.ksk.before_key_size().to_lwe_size()
+        let lanes = self.block_width - 1;
+        for (name, len) in [
+            ("first layer S-boxes", self.first_layer.len()),
+            ("second layer S-boxes", self.second_layer.len()),
+            ("first layer wires", self.first_wiring.len()),
+            ("second layer wires", self.second_wiring.len()),
+        ] {
+            if len != lanes {
+                return invalid(format!("{len} {name} for {lanes} lanes"));
             }
-        });
+        }
+
+        if let Some(wire) = self
+            .first_wiring
+            .iter()
+            .chain(self.second_wiring.iter().flatten())
+            .find(|&&wire| wire >= lanes)
+        {
+            return invalid(format!("wire to lane {wire} out of {lanes} lanes"));
+        }
+        if let Some(lane) = self.second_wiring.iter().position(Vec::is_empty) {
+            return invalid(format!("lane {lane} of the second layer has no input"));
+        }
 
-        keyround
-            .par_chunks(self.block_width)
-            .map(|block| self.filter_block(block, public_key))
-            .reduce(
-                || T::from_u4_with_lwe_size(u4(0), lwe_size),
-                |acc, block_output| acc.add(&block_output),
-            )
+        for (layer, sboxes) in [(1, &self.first_layer), (2, &self.second_layer)] {
+            if let Some(i) = sboxes.iter().position(|sbox| !is_negacyclic(sbox.as_ref())) {
+                return invalid(format!("S-box {i} of layer {layer} is not negacyclic"));
+            }
+        }
+        Ok(())
     }
+}
+
+/// Returns whether the S-box has `2^BITS` entries, and `S(x + 2^(BITS - 1)) = -S(x)` for every
+/// `x`, which `LWE::apply_sbox` relies on to evaluate the S-box with a single bootstrapping.
+pub fn is_negacyclic<const BITS: usize>(sbox: &[Uint<BITS>]) -> bool {
+    let (modulus, half) = (
+        Uint::<BITS>::MODULUS,
+        usize::from(Uint::<BITS>::MODULUS / 2),
+    );
+    sbox.len() == usize::from(modulus)
+        && sbox.iter().all(|u| u.0 < modulus)
+        && (0..half).all(|x| sbox[x + half].0 == (modulus - sbox[x].0) % modulus)
+}
+
+/// A checked filter, that computes keystream nibbles from whitened key rounds of any kind of
+/// nibble. On clear nibbles, the S-boxes and additions are evaluated in constant time.
+#[derive(Clone)]
+pub struct Filter<const BITS: usize = 4>(FilterSpec<BITS>)
+where
+    Uint<BITS>: Width;
+
+/// The S-boxes of a filter, prepared once for the evaluation on nibbles of type `T`.
+pub struct Sboxes<T: Nibble<BITS>, const BITS: usize = 4>
+where
+    Uint<BITS>: Width,
+{
+    first_layer: Vec<T::Sbox>,
+    second_layer: Vec<T::Sbox>,
+}
 
-    /// Generate a random nibble from keyround and stores it into rop.
-    /// If the key is encrypted, this function needs a public key to work.
-    #[cfg(not(feature = "multithread"))]
-    pub fn call<T: Nibble>(&self, keyround: &[T], public_key: Option<&PublicKey>) -> T {
-        keyround
-            .chunks(self.block_width)
-            .map(|block| self.filter_block(block, public_key))
-            .reduce(|acc, block_output| acc.add(&block_output))
-            .unwrap()
+impl Filter {
+    /// Generates the filter of Elisabeth-4.
+    #[must_use]
+    pub fn elisabeth4() -> Self {
+        Self(FilterSpec::elisabeth4())
     }
+}
 
-    #[cfg(feature = "multithread")]
-    #[allow(unused_mut)]
-    fn filter_block<T: Nibble>(&self, block: &[T], public_key: Option<&PublicKey>) -> T {
-        let lwe_dimension = public_key.map(|pk| pk.ksk.before_key_size().to_lwe_size());
+/// An algorithm used to generate a random nibble from the secret key.
+impl<const BITS: usize> Filter<BITS>
+where
+    Uint<BITS>: Width,
+{
+    /// Generates a new filter from its description, once checked.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidFilter` if the description fails `FilterSpec::validate`.
+    pub fn new(spec: FilterSpec<BITS>) -> Result<Self, ElisabethError> {
+        spec.validate()?;
+        Ok(Self(spec))
+    }
 
-        let mut last_block = block[self.block_width - 1].clone();
-        #[cfg(not(feature = "single_key"))]
-        last_block.keyswitch(public_key);
+    /// Returns the description of the filter.
+    #[must_use]
+    pub const fn spec(&self) -> &FilterSpec<BITS> {
+        &self.0
+    }
 
-        let first_layer_output = (0..block.len() - 1)
-            .into_par_iter()
-            .map(|i| {
-                (block[i].add(&block[(i + 1) % (self.block_width - 1)]))
-                    .apply_sbox(&self.sbox[i], public_key)
-            })
-            .collect::<Vec<_>>();
+    /// Returns the number of nibbles of the key round processed by a single block.
+    #[must_use]
+    pub const fn block_width(&self) -> usize {
+        self.0.block_width
+    }
 
-        let mut second_layer_output = (0..block.len() - 1)
-            .into_par_iter()
-            .map(|i| {
-                let mut sboxes_sum = first_layer_output[(i + 1) % (self.block_width - 1)]
-                    .add(&first_layer_output[(i + 2) % (self.block_width - 1)]);
-                sboxes_sum.keyswitch(public_key);
-                (block[i].add(&sboxes_sum)).apply_sbox(&self.sbox[4 + i], public_key)
-            })
-            .reduce(
-                || T::from_u4_with_lwe_size(u4(0), lwe_dimension),
-                |acc, output| acc.add(&output),
-            );
-        #[cfg(feature = "single_key")]
-        second_layer_output.keyswitch(public_key);
-        second_layer_output.add(&last_block)
+    /// Prepares the S-boxes of the filter for the evaluation on nibbles of type `T`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if an S-box can not be prepared for `T`.
+    pub fn sboxes<T: Nibble<BITS>>(
+        &self,
+        context: &T::EvaluationContext,
+    ) -> Result<Sboxes<T, BITS>, ElisabethError> {
+        let prepare = |layer: &[<Uint<BITS> as Width>::Table]| {
+            layer
+                .iter()
+                .map(|table| T::sbox(table.as_ref(), context))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Sboxes {
+            first_layer: prepare(&self.0.first_layer)?,
+            second_layer: prepare(&self.0.second_layer)?,
+        })
+    }
+
+    /// Computes the keystream nibble of a whitened key round, that is the sum of the outputs of
+    /// the blocks it splits into, with S-boxes prepared by `sboxes` for the same context.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LengthMismatch` if the key round is empty or does not split into blocks, or the
+    /// error of an operation on the nibbles.
+    pub fn call<T: Nibble<BITS>>(
+        &self,
+        keyround: &[T],
+        sboxes: &Sboxes<T, BITS>,
+        context: &T::EvaluationContext,
+    ) -> Result<T, ElisabethError> {
+        let mut block_outputs = keyround
+            .chunks(self.0.block_width)
+            .map(|block| self.filter_block(block, sboxes, context));
+        let first = block_outputs
+            .next()
+            .ok_or(ElisabethError::LengthMismatch {
+                expected: self.0.block_width,
+                found: 0,
+            })??;
+        block_outputs.try_fold(first, |acc, block_output| Ok(acc.add(&block_output?)))
     }
 
-    #[cfg(not(feature = "multithread"))]
-    #[allow(unused_mut)]
-    fn filter_block<T: Nibble>(&self, block: &[T], public_key: Option<&PublicKey>) -> T {
-        let mut last_block = block[self.block_width - 1].clone();
+    /// Computes the contribution of one block of the key round to the output nibble.
+    /// The output of the filter is the sum of the outputs of all its blocks.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LengthMismatch` if the block does not have `block_width` nibbles, or the error of
+    /// an operation on the nibbles.
+    // the `unwrap` can not fail, since a checked filter has at least one lane
+    #[allow(unused_mut, clippy::missing_panics_doc)]
+    pub fn filter_block<T: Nibble<BITS>>(
+        &self,
+        block: &[T],
+        sboxes: &Sboxes<T, BITS>,
+        context: &T::EvaluationContext,
+    ) -> Result<T, ElisabethError> {
+        let FilterSpec {
+            block_width,
+            first_wiring,
+            second_wiring,
+            ..
+        } = &self.0;
+        let Sboxes {
+            first_layer,
+            second_layer,
+        } = sboxes;
+        if block.len() != *block_width {
+            return Err(ElisabethError::LengthMismatch {
+                expected: *block_width,
+                found: block.len(),
+            });
+        }
+
+        let mut last_block = block[block_width - 1].clone();
         #[cfg(not(feature = "single_key"))]
-        last_block.keyswitch(public_key);
+        last_block.keyswitch(context)?;
 
-        let first_layer_output = (0..block.len() - 1)
-            .into_iter()
-            .map(|i| {
-                (block[i].add(&block[(i + 1) % (self.block_width - 1)]))
-                    .apply_sbox(&self.sbox[i], public_key)
-            })
-            .collect::<Vec<_>>();
+        let first_layer_output = (0..block_width - 1)
+            .map(|i| (block[i].add(&block[first_wiring[i]])).apply_sbox(&first_layer[i], context))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Zeroizing::new)?;
 
-        let mut second_layer_output = (0..block.len() - 1)
-            .into_iter()
+        let mut second_layer_output = (0..block_width - 1)
             .map(|i| {
-                let mut sboxes_sum = first_layer_output[(i + 1) % (self.block_width - 1)]
-                    .add(&first_layer_output[(i + 2) % (self.block_width - 1)]);
-                sboxes_sum.keyswitch(public_key);
-                (block[i].add(&sboxes_sum)).apply_sbox(&self.sbox[4 + i], public_key)
+                let mut sboxes_sum = second_wiring[i][1..].iter().fold(
+                    first_layer_output[second_wiring[i][0]].clone(),
+                    |acc, &j| acc.add(&first_layer_output[j]),
+                );
+                sboxes_sum.keyswitch(context)?;
+                (block[i].add(&sboxes_sum)).apply_sbox(&second_layer[i], context)
             })
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
             .reduce(|acc, output| acc.add(&output))
             .unwrap();
         #[cfg(feature = "single_key")]
-        second_layer_output.keyswitch(public_key);
-        second_layer_output.add(&last_block)
+        second_layer_output.keyswitch(context)?;
+        Ok(second_layer_output.add(&last_block))
     }
 }
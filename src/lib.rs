@@ -1,14 +1,34 @@
 #![warn(clippy::pedantic)]
 #![warn(clippy::nursery)]
 
+pub mod analysis;
+mod backend;
+mod bitslice;
+pub mod cost;
+pub mod debug;
 mod encrypter;
+mod error;
 mod filter;
 mod nibble;
-mod public_key;
+pub mod noise;
+pub mod packing;
+mod secret;
+mod storage;
 mod symmetric_key;
+pub mod trace;
+mod transcipherer;
+pub mod xof;
 
 pub type Torus = u64;
 
-pub use encrypter::{parameters::SystemParameters, Encrypter};
-pub use nibble::{u4, LWE};
-pub use public_key::PublicKey;
+pub use backend::{BackendKey, Concrete, FheBackend, PublicKey};
+pub use encrypter::{
+    parameters::{CustomParameters, ParameterSet, SystemParameters},
+    Encrypter,
+};
+pub use error::ElisabethError;
+pub use filter::{Filter, FilterSpec, Sboxes};
+pub use nibble::{u4, Nibble, Uint, Width, LWE};
+pub use secret::FheSecretKey;
+pub use symmetric_key::{Nonce, Round};
+pub use transcipherer::Transcipherer;
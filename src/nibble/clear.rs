@@ -1,47 +1,136 @@
-use crate::{nibble::Nibble, public_key::PublicKey};
-use concrete_core::crypto::{secret::LweSecretKey, LweSize};
+use crate::{
+    bitslice,
+    error::ElisabethError,
+    filter::Filter,
+    nibble::Nibble,
+    symmetric_key::{Nonce, SymmetricKey},
+};
 use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, fmt::Debug};
+use zeroize::Zeroize;
 
-#[allow(non_camel_case_types)]
+/// An integer modulo `2^BITS`, held in the `BITS` least significant bits of a byte.
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
-pub struct u4(pub u8);
+pub struct Uint<const BITS: usize>(pub u8);
+
+/// The nibbles of Elisabeth-4.
+#[allow(non_camel_case_types)]
+pub type u4 = Uint<4>;
+
+/// Builds a nibble of Elisabeth-4.
+#[must_use]
+pub const fn u4(u: u8) -> u4 {
+    Uint(u)
+}
+
+impl<const BITS: usize> Uint<BITS> {
+    /// The number of values of a nibble.
+    pub const MODULUS: u8 = 1 << BITS;
+    /// The mask of the bits of a nibble.
+    pub const MASK: u8 = Self::MODULUS - 1;
+}
+
+/// A supported width of nibbles, from 2 to 6 bits.
+pub trait Width: Sized {
+    /// An S-box on nibbles of this width, `S(0)` first.
+    type Table: Copy
+        + Debug
+        + Send
+        + Sync
+        + AsRef<[Self]>
+        + AsMut<[Self]>
+        + for<'a> TryFrom<&'a [Self]>;
+}
+
+macro_rules! widths {
+    ($($bits:literal),*) => {$(
+        impl Width for Uint<$bits> {
+            type Table = [Self; 1 << $bits];
+        }
+    )*};
+}
+
+widths!(2, 3, 4, 5, 6);
 
-impl Nibble for u4 {
-    fn from_u4_with_lwe_size(u: u4, _lwe_size: Option<LweSize>) -> Self {
-        u
+impl<const BITS: usize> Zeroize for Uint<BITS> {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
     }
+}
+
+impl<const BITS: usize> Nibble<BITS> for Uint<BITS>
+where
+    Self: Width,
+{
+    const CLEAR: bool = true;
+
+    type EncryptionContext = ();
+    type EvaluationContext = ();
 
-    fn from_u4(
-        u: u4,
-        _secret_key: Option<&LweSecretKey<Vec<bool>>>,
-        _std_dev: Option<f64>,
-    ) -> Self {
-        u
+    fn trivial(u: Self, _context: &()) -> Result<Self, ElisabethError> {
+        Ok(u)
     }
 
-    fn apply_sbox(&self, sbox: &[u4], _pk: Option<&PublicKey>) -> Self {
-        sbox[self.0 as usize]
+    fn encrypt(u: Self, _context: &()) -> Result<Self, ElisabethError> {
+        Ok(u)
+    }
+
+    type Sbox = <Self as Width>::Table;
+
+    fn sbox(table: &[Self], _context: &()) -> Result<Self::Sbox, ElisabethError> {
+        Self::Sbox::try_from(table).map_err(|_| {
+            ElisabethError::InvalidFilter(format!(
+                "an S-box on {} bits has {} entries, not {}",
+                BITS,
+                1 << BITS,
+                table.len()
+            ))
+        })
+    }
+
+    /// Reads every entry of the S-box, and keeps the right one with a mask, so that neither the
+    /// memory accesses nor the timing depend on the nibble.
+    fn apply_sbox(&self, sbox: &Self::Sbox, _context: &()) -> Result<Self, ElisabethError> {
+        Ok(Self(sbox.as_ref().iter().enumerate().fold(
+            0,
+            |res, (x, y)| {
+                let mask = ((x as u8 ^ self.0) as u16).wrapping_sub(1) >> 8;
+                res | (y.0 & mask as u8)
+            },
+        )))
     }
 
     fn negate(&mut self) {
-        self.0 = ((1 << 4) - self.0) % (1 << 4)
+        self.0 = self.0.wrapping_neg() & Self::MASK;
     }
 
-    fn keyswitch(&mut self, _pk: Option<&PublicKey>) {}
+    fn keyswitch(&mut self, _context: &()) -> Result<(), ElisabethError> {
+        Ok(())
+    }
 
     fn add(&self, rhs: &Self) -> Self {
-        u4((self.0 + rhs.0) % (1 << 4))
+        Self(self.0.wrapping_add(rhs.0) & Self::MASK)
     }
 
-    fn add_u4(&self, rhs: &u4) -> Self {
+    fn add_u4(&self, rhs: &Self) -> Self {
         self.add(rhs)
     }
 
     fn add_assign(&mut self, rhs: &Self) {
-        self.0 = (self.0 + rhs.0) % (1 << 4)
+        self.0 = self.0.wrapping_add(rhs.0) & Self::MASK;
     }
 
-    fn add_assign_u4(&mut self, rhs: &u4) {
+    fn add_assign_u4(&mut self, rhs: &Self) {
         self.add_assign(rhs);
     }
+
+    fn bitsliced_keystream(
+        filter: &Filter<BITS>,
+        key: &SymmetricKey<Self>,
+        nonce: Nonce,
+        offset: u64,
+        len: usize,
+    ) -> Option<Result<Vec<Self>, ElisabethError>> {
+        Some(bitslice::keystream(filter, key, nonce, offset, len))
+    }
 }
@@ -1,40 +1,94 @@
 mod clear;
 mod lwe;
 
-use concrete_core::crypto::{secret::LweSecretKey, LweSize};
 use serde::{de::DeserializeOwned, Serialize};
+use zeroize::Zeroize;
 
-pub use clear::u4;
+pub use clear::{u4, Uint, Width};
 pub use lwe::LWE;
 
-use crate::public_key::PublicKey;
+use crate::{
+    error::ElisabethError,
+    filter::Filter,
+    symmetric_key::{Nonce, SymmetricKey},
+};
 
-/// Generic implementation of a nibble, that is a 4-bit integer that may or may not be encrypted.
-pub trait Nibble: Clone + Sync + Send + Serialize + DeserializeOwned {
-    /// Converts a u4 to a nibble.
-    /// If the nibble is encrypted, this function needs the LWE size to work. It will then output a trivial encryption.
-    fn from_u4_with_lwe_size(u: u4, lwe_size: Option<LweSize>) -> Self;
+/// Generic implementation of a nibble, that is a `BITS`-bit integer that may or may not be
+/// encrypted. Since clear nibbles may hold secret material, every nibble can be wiped from memory.
+///
+/// Each kind of nibble brings the material it needs to be encrypted and evaluated, so that the
+/// compiler checks that it is given: clear nibbles need none, while LWE nibbles need a secret key
+/// to be encrypted and the public key to be evaluated.
+// what makes an operation fail depends on the kind of nibble, and is documented with it
+#[allow(clippy::missing_errors_doc)]
+pub trait Nibble<const BITS: usize = 4>:
+    Clone + Sync + Send + Serialize + DeserializeOwned + Zeroize
+where
+    Uint<BITS>: Width,
+{
+    /// Whether the nibble holds its value in the clear. A symmetric key made of such nibbles is
+    /// secret material, and is kept out of swap while in memory. It is also the one of a client,
+    /// so its usage is saved across restarts.
+    const CLEAR: bool = false;
 
-    /// Converts a u4 to a nibble.
-    /// If the nibble is encrypted, this function needs the LWE secret key and a standard_deviation to work.
-    fn from_u4(u: u4, secret_key: Option<&LweSecretKey<Vec<bool>>>, std_dev: Option<f64>) -> Self;
+    /// The material needed to encrypt a nibble.
+    type EncryptionContext;
+
+    /// The material needed to compute on nibbles, shared by all the threads of an encrypter.
+    type EvaluationContext: Sync + Send;
+
+    /// Converts a clear nibble to a nibble, without encrypting it.
+    /// If the nibble is encrypted, the output is a trivial encryption under the input key of the
+    /// bootstrappings.
+    fn trivial(u: Uint<BITS>, context: &Self::EvaluationContext) -> Result<Self, ElisabethError>;
+
+    /// Encrypts a clear nibble.
+    fn encrypt(u: Uint<BITS>, context: &Self::EncryptionContext) -> Result<Self, ElisabethError>;
+
+    /// An S-box, in the form in which it is applied to this kind of nibble.
+    type Sbox: Send + Sync;
+
+    /// Prepares the given S-box, of `2^BITS` entries, once for all the nibbles it will be applied
+    /// to.
+    fn sbox(
+        table: &[Uint<BITS>],
+        context: &Self::EvaluationContext,
+    ) -> Result<Self::Sbox, ElisabethError>;
 
     /// Applies the given S-box to the nibble.
-    /// If the nibble is encrypted, this function needs the LWE public key to work.
-    fn apply_sbox(&self, sbox: &[u4], pk: Option<&PublicKey>) -> Self;
+    fn apply_sbox(
+        &self,
+        sbox: &Self::Sbox,
+        context: &Self::EvaluationContext,
+    ) -> Result<Self, ElisabethError>;
 
     /// Negates the nibble inplace.
     fn negate(&mut self);
 
     /// If this nibble is encrypted, changes its parameters so that they match a given standard.
     /// This is useful to map the ciphertext to some parameters at the end of the transcryption.
-    fn keyswitch(&mut self, pk: Option<&PublicKey>);
+    fn keyswitch(&mut self, context: &Self::EvaluationContext) -> Result<(), ElisabethError>;
 
+    #[must_use]
     fn add(&self, rhs: &Self) -> Self;
 
-    fn add_u4(&self, rhs: &u4) -> Self;
+    #[must_use]
+    fn add_u4(&self, rhs: &Uint<BITS>) -> Self;
 
     fn add_assign(&mut self, rhs: &Self);
 
-    fn add_assign_u4(&mut self, rhs: &u4);
+    fn add_assign_u4(&mut self, rhs: &Uint<BITS>);
+
+    /// Computes the nibbles `offset..offset + len` of the keystream selected by `nonce`, if this
+    /// kind of nibble has a faster way to do so than evaluating the filter one nibble at a time.
+    #[must_use]
+    fn bitsliced_keystream(
+        _filter: &Filter<BITS>,
+        _key: &SymmetricKey<Self>,
+        _nonce: Nonce,
+        _offset: u64,
+        _len: usize,
+    ) -> Option<Result<Vec<Self>, ElisabethError>> {
+        None
+    }
 }
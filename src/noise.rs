@@ -0,0 +1,373 @@
+//! Analytic estimate of the noise of the transciphered nibbles.
+//!
+//! A `Noise` is a nibble which holds no ciphertext, but the variance of the noise that an `LWE`
+//! would carry after the same operations, along with the probability that one of its
+//! bootstrappings went wrong. Evaluating the filter on `Noise` nibbles gives the variance and the
+//! failure probability of a transciphered nibble, reported by `SystemParameters::noise`.
+//!
+//! Variances are given on the torus, that is relative to the square of the modulus `2^64`, for
+//! binary secret keys, whose coefficients have a mean square of `1/2`. With `B` the base and `l`
+//! the number of levels of a decomposition:
+//! - a fresh encryption has the variance `σ²` of its key;
+//! - additions add the variances, while negations and additions of a clear nibble keep it;
+//! - a keyswitching from a key of dimension `m`, with a key of variance `σ²`, adds
+//!   `m l σ² (B² + 2) / 12 + m / (24 B^(2l))`;
+//! - a bootstrapping first rounds the `n + 1` coefficients of its input to multiples of `1 / 2N`,
+//!   which adds `(n / 2 + 1) / (48 N²)`. It then outputs a variance of
+//!   `n ((k + 1) l N σ² (B² + 2) / 12 + (k N / 2 + 1) / (24 B^(2l)))`, whatever the input, for a
+//!   GLWE of dimension `k` and polynomial size `N`, and a bootstrapping key of variance `σ²`.
+//!
+//! Nibbles are encoded in the 4 most significant bits of the torus, so that a nibble is decrypted,
+//! and an S-box evaluated, correctly as long as its noise stays below `1/32`. A Gaussian noise of
+//! variance `v` exceeds it with probability `erfc(1 / (32 sqrt(2 v)))`. The failure probability of
+//! a nibble is bounded by the sum of these probabilities over all the bootstrappings it depends on
+//! and its final decryption.
+
+// the formulas are kept in the form above rather than with fused multiply-adds, and the dimensions
+// they convert to `f64` are far below `2^52`
+#![allow(clippy::suboptimal_flops, clippy::cast_precision_loss)]
+
+use crate::{error::ElisabethError, nibble::Nibble, u4, SystemParameters};
+use concrete_commons::DispersionParameter;
+use concrete_core::math::decomposition::{DecompositionBaseLog, DecompositionLevelCount};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+/// The highest noise, on the torus, with which a nibble is still decrypted correctly: half the gap
+/// between two nibbles of 4 bits. The estimates only hold for nibbles of 4 bits, the widest that
+/// the FHE parameters support.
+const MAX_NOISE: f64 = 1. / 32.;
+
+/// A decomposition of a keyswitching or bootstrapping key.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct Decomposition {
+    base_log: usize,
+    level: usize,
+}
+
+impl Decomposition {
+    const fn new((base_log, level): (DecompositionBaseLog, DecompositionLevelCount)) -> Self {
+        Self {
+            base_log: base_log.0,
+            level: level.0,
+        }
+    }
+
+    /// The variance of the rounding of a torus element to the closest decomposable value.
+    fn rounding(self) -> f64 {
+        f64::exp2(-2. * (self.base_log * self.level) as f64) / 24.
+    }
+
+    /// The sum of the variances of the `(B² + 2) / 12` factor, over the levels.
+    fn levels(self) -> f64 {
+        self.level as f64 * (f64::exp2(2. * self.base_log as f64) + 2.) / 12.
+    }
+}
+
+/// The parameters of the keys of a preset that the noise depends on.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NoiseModel {
+    /// The dimension of the input key of the bootstrappings.
+    lwe_dimension: usize,
+    lwe_variance: f64,
+    glwe_dimension: usize,
+    polynomial_size: usize,
+    glwe_variance: f64,
+    bsk: Decomposition,
+    ksk: Decomposition,
+    #[cfg(not(feature = "single_key"))]
+    ksk_inv: Decomposition,
+}
+
+impl NoiseModel {
+    /// Gets the parameters of the keys of the given preset.
+    #[cfg(not(feature = "single_key"))]
+    #[must_use]
+    pub fn new(params: &SystemParameters) -> Self {
+        let ((lwe_dimension, lwe), (glwe_dimension, polynomial_size, glwe), bsk, ksk, ksk_inv) =
+            params.fhe_parameters();
+
+        Self {
+            lwe_dimension: lwe_dimension.0,
+            lwe_variance: lwe.get_variance(),
+            glwe_dimension: glwe_dimension.0,
+            polynomial_size: polynomial_size.0,
+            glwe_variance: glwe.get_variance(),
+            bsk: Decomposition::new(bsk),
+            ksk: Decomposition::new(ksk),
+            ksk_inv: Decomposition::new(ksk_inv),
+        }
+    }
+
+    /// Gets the parameters of the keys of the given preset.
+    #[cfg(feature = "single_key")]
+    #[must_use]
+    pub fn new(params: &SystemParameters) -> Self {
+        let ((lwe_dimension, lwe), (glwe_dimension, polynomial_size, glwe), bsk, ksk) =
+            params.fhe_parameters();
+
+        Self {
+            lwe_dimension: lwe_dimension.0,
+            lwe_variance: lwe.get_variance(),
+            glwe_dimension: glwe_dimension.0,
+            polynomial_size: polynomial_size.0,
+            glwe_variance: glwe.get_variance(),
+            bsk: Decomposition::new(bsk),
+            ksk: Decomposition::new(ksk),
+        }
+    }
+
+    /// Returns the estimate of a fresh encryption under the input key of the bootstrappings, as
+    /// the nibbles of the encrypted symmetric key.
+    #[must_use]
+    pub const fn fresh(&self) -> Noise {
+        Noise {
+            variance: self.lwe_variance,
+            key: Key::Input,
+            log2_failure: f64::NEG_INFINITY,
+            max_bootstrap_variance: 0.,
+        }
+    }
+
+    /// The variance added by a keyswitching from a key of the given dimension.
+    fn keyswitch(dimension: usize, decomposition: Decomposition, variance: f64) -> f64 {
+        dimension as f64 * (decomposition.levels() * variance + decomposition.rounding())
+    }
+
+    /// The variance added by the modulus switching of a bootstrapping.
+    fn modulus_switch(&self) -> f64 {
+        (self.lwe_dimension as f64 / 2. + 1.) / (48. * f64::powi(self.polynomial_size as f64, 2))
+    }
+
+    /// The variance of the output of a bootstrapping.
+    fn bootstrap(&self) -> f64 {
+        let (k, big_n) = (self.glwe_dimension as f64, self.polynomial_size as f64);
+        self.lwe_dimension as f64
+            * ((k + 1.) * big_n * self.bsk.levels() * self.glwe_variance
+                + (k * big_n / 2. + 1.) * self.bsk.rounding())
+    }
+}
+
+/// The key a `Noise` is encrypted under.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum Key {
+    /// The input key of the bootstrappings.
+    Input,
+    /// The output key of the bootstrappings.
+    Output,
+}
+
+/// The estimated noise of an LWE ciphertext.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Noise {
+    variance: f64,
+    key: Key,
+    log2_failure: f64,
+    max_bootstrap_variance: f64,
+}
+
+impl Noise {
+    /// Returns the variance of the noise, on the torus.
+    #[must_use]
+    pub const fn variance(&self) -> f64 {
+        self.variance
+    }
+
+    /// Returns the base 2 logarithm of the probability that a bootstrapping this nibble depends on
+    /// went wrong.
+    #[must_use]
+    pub const fn log2_bootstrap_failure(&self) -> f64 {
+        self.log2_failure
+    }
+
+    /// Returns the base 2 logarithm of the probability that this nibble is not decrypted
+    /// correctly, one of its bootstrappings having failed or its noise being too large.
+    #[must_use]
+    pub fn log2_failure(&self) -> f64 {
+        log2_sum(self.log2_failure, log2_exceeds(self.variance))
+    }
+
+    /// Returns the highest variance at the input of the bootstrappings this nibble depends on,
+    /// modulus switching included.
+    #[must_use]
+    pub const fn max_bootstrap_variance(&self) -> f64 {
+        self.max_bootstrap_variance
+    }
+
+    fn check_key(&self, key: Key, operation: &str) -> Result<(), ElisabethError> {
+        if self.key == key {
+            Ok(())
+        } else {
+            Err(ElisabethError::ParameterMismatch(format!(
+                "a nibble under the {:?} key cannot go through a {}",
+                self.key, operation
+            )))
+        }
+    }
+}
+
+/// The estimated noise of the transciphered nibbles of a preset. Since the nibbles of the
+/// symmetric key all have the same noise, so do all the transciphered nibbles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseReport {
+    /// The variance of the noise of a transciphered nibble, on the torus.
+    pub variance: f64,
+    /// The highest variance at the input of a bootstrapping, modulus switching included.
+    pub max_bootstrap_variance: f64,
+    /// The base 2 logarithm of the probability that a bootstrapping of a transciphered nibble
+    /// fails.
+    pub log2_bootstrap_failure: f64,
+    /// The base 2 logarithm of the probability that a transciphered nibble is not decrypted
+    /// correctly.
+    pub log2_failure: f64,
+}
+
+impl SystemParameters {
+    /// Estimates the noise of the nibbles transciphered with this preset, by evaluating its filter
+    /// on estimates of the noise of the encrypted symmetric key.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the filter of the preset can not be evaluated.
+    pub fn noise(&self) -> Result<NoiseReport, ElisabethError> {
+        let parameters = self.parameters();
+        let model = NoiseModel::new(self);
+        let key_round = vec![model.fresh(); parameters.n];
+
+        // the keystream is then negated and the ciphertext added to it, which keeps the noise
+        let nibble =
+            parameters
+                .filter
+                .call(&key_round, &parameters.filter.sboxes(&model)?, &model)?;
+
+        Ok(NoiseReport {
+            variance: nibble.variance(),
+            max_bootstrap_variance: nibble.max_bootstrap_variance(),
+            log2_bootstrap_failure: nibble.log2_bootstrap_failure(),
+            log2_failure: nibble.log2_failure(),
+        })
+    }
+}
+
+/// An estimate holds no secret.
+impl Zeroize for Noise {
+    fn zeroize(&mut self) {}
+}
+
+impl Nibble for Noise {
+    type EncryptionContext = NoiseModel;
+    type EvaluationContext = NoiseModel;
+
+    fn trivial(_u: u4, model: &NoiseModel) -> Result<Self, ElisabethError> {
+        Ok(Self {
+            variance: 0.,
+            ..model.fresh()
+        })
+    }
+
+    fn encrypt(_u: u4, model: &NoiseModel) -> Result<Self, ElisabethError> {
+        Ok(model.fresh())
+    }
+
+    /// The noise does not depend on the S-box.
+    type Sbox = ();
+
+    fn sbox(_table: &[u4], _model: &NoiseModel) -> Result<Self::Sbox, ElisabethError> {
+        Ok(())
+    }
+
+    fn apply_sbox(&self, _sbox: &(), model: &NoiseModel) -> Result<Self, ElisabethError> {
+        self.check_key(Key::Input, "bootstrapping")?;
+        let input = self.variance + model.modulus_switch();
+        Ok(Self {
+            variance: model.bootstrap(),
+            key: Key::Output,
+            log2_failure: log2_sum(self.log2_failure, log2_exceeds(input)),
+            max_bootstrap_variance: self.max_bootstrap_variance.max(input),
+        })
+    }
+
+    fn negate(&mut self) {}
+
+    #[cfg(not(feature = "single_key"))]
+    fn keyswitch(&mut self, model: &NoiseModel) -> Result<(), ElisabethError> {
+        let (key, added) = match self.key {
+            Key::Output => (
+                Key::Input,
+                NoiseModel::keyswitch(
+                    model.glwe_dimension * model.polynomial_size,
+                    model.ksk,
+                    model.lwe_variance,
+                ),
+            ),
+            Key::Input => (
+                Key::Output,
+                NoiseModel::keyswitch(model.lwe_dimension, model.ksk_inv, model.glwe_variance),
+            ),
+        };
+        self.variance += added;
+        self.key = key;
+        Ok(())
+    }
+
+    #[cfg(feature = "single_key")]
+    fn keyswitch(&mut self, model: &NoiseModel) -> Result<(), ElisabethError> {
+        self.check_key(Key::Output, "keyswitching")?;
+        self.variance += NoiseModel::keyswitch(
+            model.glwe_dimension * model.polynomial_size,
+            model.ksk,
+            model.lwe_variance,
+        );
+        self.key = Key::Input;
+        Ok(())
+    }
+
+    fn add(&self, rhs: &Self) -> Self {
+        let mut res = *self;
+        res.add_assign(rhs);
+        res
+    }
+
+    fn add_u4(&self, _rhs: &u4) -> Self {
+        *self
+    }
+
+    /// Unlike `LWE::add_assign`, adding nibbles under different keys does not panic, but the
+    /// result is meaningless.
+    fn add_assign(&mut self, rhs: &Self) {
+        self.variance += rhs.variance;
+        self.log2_failure = log2_sum(self.log2_failure, rhs.log2_failure);
+        self.max_bootstrap_variance = self.max_bootstrap_variance.max(rhs.max_bootstrap_variance);
+    }
+
+    fn add_assign_u4(&mut self, _rhs: &u4) {}
+}
+
+/// Returns `log2(2^a + 2^b)`, without leaving the logarithmic domain.
+fn log2_sum(a: f64, b: f64) -> f64 {
+    let (high, low) = if a > b { (a, b) } else { (b, a) };
+    if low == f64::NEG_INFINITY {
+        high
+    } else {
+        high + f64::log2(1. + f64::exp2(low - high))
+    }
+}
+
+/// Returns the base 2 logarithm of the probability that a centered Gaussian noise of the given
+/// variance exceeds `MAX_NOISE` in absolute value, that is `erfc(MAX_NOISE / sqrt(2 variance))`.
+///
+/// The complementary error function is approximated as in Numerical Recipes, with a relative
+/// error below `1.2e-7`, and its logarithm computed directly so that it never underflows.
+fn log2_exceeds(variance: f64) -> f64 {
+    let z = MAX_NOISE / f64::sqrt(2. * variance);
+    let t = 1. / (1. + 0.5 * z);
+    let ln_erfc = t.ln() - z * z - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98
+                                + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    ln_erfc / std::f64::consts::LN_2
+}
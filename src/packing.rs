@@ -0,0 +1,404 @@
+//! Packing of transciphered nibbles into GLWE ciphertexts.
+//!
+//! A transciphered nibble is an LWE ciphertext of several hundred 64-bit words for a few bits of
+//! message. A packing keyswitching folds up to `N` of them, `N` being the polynomial size of the
+//! packing key, into a single GLWE ciphertext of `(k + 1) N` words, the `i`-th nibble being the
+//! `i`-th coefficient of its plaintext polynomial. With the parameters of the presets, this is 4
+//! words per nibble instead of 1537, or 864 in single key mode, so that large transciphered
+//! datasets can be stored and shipped compactly.
+//!
+//! The packing key holds, for every bit `s[j]` of the key of the nibbles and every level `v` of
+//! the decomposition, an encryption of the constant polynomial `s[j] q / B^(v + 1)` under a GLWE
+//! secret key of its own. An LWE ciphertext `(a, b)` is packed into
+//! `X^i ((0, b) - sum over j and v of d[j][v] K[j][v])`, where `d[j][v]` are the signed digits of
+//! `a[j]` in base `B`. This adds to each nibble the rounding error of the decomposition and the
+//! noise of the key, whose standard deviation is about `2^-13` with `packing_parameters`, far
+//! below the one of a transciphered nibble.
+//!
+//! Packed nibbles are unpacked by sample extraction, which needs no key: the `i`-th coefficient of
+//! a GLWE ciphertext is an LWE ciphertext of dimension `k N` under the flattened GLWE secret key.
+
+use crate::{
+    backend::Concrete,
+    error::ElisabethError,
+    nibble::{Uint, Width},
+    secret::FheSecretKey,
+    Torus, LWE,
+};
+use concrete_commons::StandardDev;
+use concrete_core::{
+    crypto::{
+        cross::constant_sample_extract,
+        encoding::PlaintextList,
+        glwe::{GlweCiphertext, GlweList},
+        lwe::LweCiphertext,
+        secret::GlweSecretKey,
+        CiphertextCount, GlweDimension, GlweSize, LweDimension, PlaintextCount,
+    },
+    math::{
+        decomposition::{
+            DecompositionBaseLog, DecompositionLevel, DecompositionLevelCount, SignedDecomposable,
+        },
+        polynomial::{MonomialDegree, PolynomialSize},
+        random::{EncryptionRandomGenerator, RandomGenerator},
+        tensor::{AsMutSlice, AsMutTensor, AsRefSlice, AsRefTensor, Tensor},
+    },
+};
+#[cfg(feature = "multithread")]
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+/// The public key of the packing keyswitching.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PackingKey {
+    /// For each bit of the input key, the encryptions of its levels, the most significant first.
+    key: GlweList<Vec<Torus>>,
+    base_log: DecompositionBaseLog,
+    level_count: DecompositionLevelCount,
+}
+
+impl PackingKey {
+    /// Encrypts the decomposition of every bit of `input` under the GLWE secret key `output`,
+    /// given in its flattened form, of `k N` bits.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParameterMismatch` if `output` does not split into polynomials of
+    /// `polynomial_size` bits.
+    pub fn new(
+        input: &FheSecretKey,
+        output: &FheSecretKey,
+        polynomial_size: PolynomialSize,
+        std_dev: StandardDev,
+        (base_log, level_count): (DecompositionBaseLog, DecompositionLevelCount),
+    ) -> Result<Self, ElisabethError> {
+        let output = glwe_key(output, polynomial_size)?;
+        let count = input.key_size().0 * level_count.0;
+
+        let mut plaintexts = PlaintextList::allocate(0, PlaintextCount(count * polynomial_size.0));
+        let coefficients = plaintexts.as_mut_tensor().as_mut_slice();
+        for (j, &bit) in input.as_tensor().iter().enumerate() {
+            for level in 0..level_count.0 {
+                coefficients[(j * level_count.0 + level) * polynomial_size.0] =
+                    Torus::from(bit).set_val_at_level(base_log, DecompositionLevel(level));
+            }
+        }
+
+        let mut key = GlweList::allocate(
+            0,
+            polynomial_size,
+            output.key_size(),
+            CiphertextCount(count),
+        );
+        output.encrypt_glwe_list(
+            &mut key,
+            &plaintexts,
+            std_dev,
+            &mut EncryptionRandomGenerator::new(None),
+        );
+        Ok(Self {
+            key,
+            base_log,
+            level_count,
+        })
+    }
+
+    /// Returns the dimension of the LWE ciphertexts that can be packed.
+    #[must_use]
+    pub fn input_dimension(&self) -> LweDimension {
+        LweDimension(self.key.ciphertext_count().0 / self.level_count.0)
+    }
+
+    /// Returns the number of nibbles a GLWE ciphertext can hold.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.key.polynomial_size().0
+    }
+
+    /// Packs up to `capacity` nibbles into a single GLWE ciphertext.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParameterMismatch` if there are more than `capacity` nibbles, or if one of them
+    /// is not of the input dimension of the key.
+    pub fn pack<const BITS: usize>(
+        &self,
+        nibbles: &[LWE<Concrete, BITS>],
+    ) -> Result<Packed<BITS>, ElisabethError>
+    where
+        Uint<BITS>: Width,
+    {
+        if nibbles.len() > self.capacity() {
+            return Err(ElisabethError::ParameterMismatch(format!(
+                "{} nibbles cannot be packed into a GLWE ciphertext of polynomial size {}",
+                nibbles.len(),
+                self.capacity()
+            )));
+        }
+        let input_size = self.input_dimension().to_lwe_size();
+        if let Some(nibble) = nibbles
+            .iter()
+            .find(|nibble| nibble.as_lwe().lwe_size() != input_size)
+        {
+            return Err(ElisabethError::ParameterMismatch(format!(
+                "an LWE ciphertext of size {} cannot be packed with a packing key of input size {}",
+                nibble.as_lwe().lwe_size().0,
+                input_size.0
+            )));
+        }
+
+        #[cfg(not(feature = "multithread"))]
+        let glwe = nibbles
+            .iter()
+            .enumerate()
+            .map(|(i, nibble)| self.keyswitch(nibble.as_lwe(), i))
+            .fold(self.zero(), |lhs, rhs| sum(lhs, &rhs));
+        #[cfg(feature = "multithread")]
+        let glwe = nibbles
+            .par_iter()
+            .enumerate()
+            .map(|(i, nibble)| self.keyswitch(nibble.as_lwe(), i))
+            .reduce(|| self.zero(), |lhs, rhs| sum(lhs, &rhs));
+
+        Ok(Packed {
+            glwe,
+            len: nibbles.len(),
+        })
+    }
+
+    /// Packs any number of nibbles into as many GLWE ciphertexts as needed.
+    ///
+    /// # Errors
+    ///
+    /// Fails as `pack`.
+    pub fn pack_all<const BITS: usize>(
+        &self,
+        nibbles: &[LWE<Concrete, BITS>],
+    ) -> Result<Vec<Packed<BITS>>, ElisabethError>
+    where
+        Uint<BITS>: Width,
+    {
+        nibbles
+            .chunks(self.capacity())
+            .map(|chunk| self.pack(chunk))
+            .collect()
+    }
+
+    fn glwe_size(&self) -> GlweSize {
+        self.key.glwe_size()
+    }
+
+    fn zero(&self) -> GlweCiphertext<Vec<Torus>> {
+        GlweCiphertext::allocate(0, self.key.polynomial_size(), self.glwe_size())
+    }
+
+    /// Switches the LWE ciphertext to a GLWE ciphertext encrypting its message times `X^degree`.
+    fn keyswitch(
+        &self,
+        lwe: &LweCiphertext<Vec<Torus>>,
+        degree: usize,
+    ) -> GlweCiphertext<Vec<Torus>> {
+        let (base_log, level_count) = (self.base_log, self.level_count);
+        let glwe_len = self.glwe_size().0 * self.key.polynomial_size().0;
+
+        let mut output = self.zero();
+        let (body, mask) = lwe.get_body_and_mask();
+        output.get_mut_body().as_mut_tensor().as_mut_slice()[0] = body.0;
+
+        let mut digits = vec![0; level_count.0];
+        for (a, levels) in mask.mask_element_iter().zip(
+            self.key
+                .as_tensor()
+                .as_slice()
+                .chunks(level_count.0 * glwe_len),
+        ) {
+            // the signed digits of the rounded mask element, from the least significant one
+            let rounded = a.round_to_closest_multiple(base_log, level_count);
+            let mut carry = 0;
+            for level in (0..level_count.0).rev() {
+                let (digit, next) =
+                    rounded.signed_decompose_one_level(carry, base_log, DecompositionLevel(level));
+                digits[level] = digit;
+                carry = next;
+            }
+
+            for (&digit, encryption) in digits.iter().zip(levels.chunks(glwe_len)) {
+                output.as_mut_tensor().update_with_wrapping_sub_element_mul(
+                    &Tensor::from_container(encryption),
+                    digit,
+                );
+            }
+        }
+
+        output
+            .as_mut_polynomial_list()
+            .update_with_wrapping_monic_monomial_mul(MonomialDegree(degree));
+        output
+    }
+}
+
+fn sum(
+    mut lhs: GlweCiphertext<Vec<Torus>>,
+    rhs: &GlweCiphertext<Vec<Torus>>,
+) -> GlweCiphertext<Vec<Torus>> {
+    for (l, r) in lhs
+        .as_mut_tensor()
+        .as_mut_slice()
+        .iter_mut()
+        .zip(rhs.as_tensor().as_slice())
+    {
+        *l = l.wrapping_add(*r);
+    }
+    lhs
+}
+
+/// Views a flattened GLWE secret key as a GLWE secret key of the given polynomial size.
+fn glwe_key(
+    key: &FheSecretKey,
+    polynomial_size: PolynomialSize,
+) -> Result<GlweSecretKey<&[bool]>, ElisabethError> {
+    let bits = key.as_tensor().as_slice();
+    if bits.is_empty() || !bits.len().is_multiple_of(polynomial_size.0) {
+        return Err(ElisabethError::ParameterMismatch(format!(
+            "a key of {} bits is not a GLWE secret key of polynomial size {}",
+            bits.len(),
+            polynomial_size.0
+        )));
+    }
+    Ok(GlweSecretKey::from_container(bits, polynomial_size))
+}
+
+/// Up to `N` nibbles of `BITS` bits packed into a GLWE ciphertext.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Packed<const BITS: usize = 4>
+where
+    Uint<BITS>: Width,
+{
+    glwe: GlweCiphertext<Vec<Torus>>,
+    len: usize,
+}
+
+impl<const BITS: usize> Packed<BITS>
+where
+    Uint<BITS>: Width,
+{
+    /// Returns the GLWE ciphertext, whose first `len` coefficients are the nibbles.
+    #[must_use]
+    pub const fn as_glwe(&self) -> &GlweCiphertext<Vec<Torus>> {
+        &self.glwe
+    }
+
+    /// Returns the number of packed nibbles.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Extracts the `index`-th nibble, as an LWE ciphertext under the flattened packing secret key.
+    #[must_use]
+    pub fn extract(&self, index: usize) -> Option<LWE<Concrete, BITS>> {
+        if index >= self.len {
+            return None;
+        }
+        let polynomial_size = self.glwe.polynomial_size();
+        let mut rotated = self.glwe.clone();
+        rotated
+            .as_mut_polynomial_list()
+            .update_with_wrapping_monic_monomial_div(MonomialDegree(index));
+
+        let dimension = LweDimension(self.glwe.mask_size().0 * polynomial_size.0);
+        let mut lwe = LweCiphertext::allocate(0, dimension.to_lwe_size());
+        constant_sample_extract(&mut lwe, &rotated);
+        Some(LWE::from_lwe(lwe))
+    }
+
+    /// Extracts all the nibbles.
+    #[must_use]
+    pub fn unpack(&self) -> Vec<LWE<Concrete, BITS>> {
+        (0..self.len).filter_map(|i| self.extract(i)).collect()
+    }
+
+    /// Decrypts all the nibbles at once with the packing secret key.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ParameterMismatch` if `key` is not a GLWE secret key of the dimension and
+    /// polynomial size of the ciphertext.
+    // the shifted coefficients only have `BITS` bits
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn decrypt(&self, key: &FheSecretKey) -> Result<Vec<Uint<BITS>>, ElisabethError> {
+        let polynomial_size = self.glwe.polynomial_size();
+        let key = glwe_key(key, polynomial_size)?;
+        if key.key_size() != self.glwe.mask_size() {
+            return Err(ElisabethError::ParameterMismatch(format!(
+                "a GLWE secret key of dimension {} cannot decrypt a GLWE ciphertext of dimension {}",
+                key.key_size().0,
+                self.glwe.mask_size().0
+            )));
+        }
+        let mut encoded = PlaintextList::allocate(0, PlaintextCount(polynomial_size.0));
+        key.decrypt_glwe(&mut encoded, &self.glwe);
+
+        let shift = Torus::BITS as usize - BITS;
+        Ok(encoded.as_tensor().as_slice()[..self.len]
+            .iter()
+            .map(|&u| {
+                let rounded = u.wrapping_add(1 << (shift - 1)) >> shift;
+                Uint(rounded as u8 & Uint::<BITS>::MASK)
+            })
+            .collect())
+    }
+}
+
+/// Returns the GLWE dimension, polynomial size and noise of the packing secret key, and the
+/// decomposition of the packing key.
+///
+/// They do not depend on the preset, as all of them share the FHE parameters of `n60`. The packing
+/// secret key has the GLWE dimension, polynomial size and noise of the GLWE key of its
+/// bootstrapping key, so that the packing key is as secure as the bootstrapping key, which is
+/// also a list of GLWE encryptions of bits of an LWE secret key. The decomposition only sets the
+/// noise added to the packed nibbles, not the security of the key.
+#[must_use]
+pub fn packing_parameters() -> (
+    (GlweDimension, PolynomialSize, StandardDev),
+    (DecompositionBaseLog, DecompositionLevelCount),
+) {
+    (
+        (
+            GlweDimension(3),
+            PolynomialSize(512),
+            StandardDev::from_standard_dev(f64::exp2(-38.4997)),
+        ),
+        (DecompositionBaseLog(16), DecompositionLevelCount(1)),
+    )
+}
+
+/// Generates a new packing secret key, in its flattened form, and the packing key of the nibbles
+/// encrypted under `input`.
+///
+/// `input` is the output key of the bootstrappings, in which the transciphered nibbles are, or
+/// their input key in single key mode.
+///
+/// # Errors
+///
+/// Fails as `PackingKey::new`.
+pub fn generate_packing_keys(
+    input: &FheSecretKey,
+) -> Result<(FheSecretKey, PackingKey), ElisabethError> {
+    let ((glwe_dimension, polynomial_size, std_dev), decomposition) = packing_parameters();
+    let output = FheSecretKey::new(
+        GlweSecretKey::generate(
+            glwe_dimension,
+            polynomial_size,
+            &mut RandomGenerator::new(None),
+        )
+        .into_lwe_secret_key(),
+    );
+    let key = PackingKey::new(input, &output, polynomial_size, std_dev, decomposition)?;
+    Ok((output, key))
+}
@@ -0,0 +1,71 @@
+//! Handling of secret material: it is wiped from memory when dropped and, where the platform
+//! allows it, kept out of swap.
+
+use concrete_core::{
+    crypto::secret::LweSecretKey,
+    math::tensor::{AsMutSlice, AsMutTensor, AsRefSlice, AsRefTensor},
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::Deref;
+use zeroize::Zeroize;
+
+/// Prevents the memory holding `data` from being swapped out.
+/// This is done on a best effort basis, and silently does nothing if the platform refuses it.
+pub fn lock<T>(data: &[T]) {
+    #[cfg(unix)]
+    if !data.is_empty() {
+        // SAFETY: the range is the one of a live slice, and mlock does not access it.
+        unsafe {
+            libc::mlock(data.as_ptr().cast(), std::mem::size_of_val(data));
+        }
+    }
+}
+
+/// Allows the memory holding `data` to be swapped out again.
+pub fn unlock<T>(data: &[T]) {
+    #[cfg(unix)]
+    if !data.is_empty() {
+        // SAFETY: the range is the one of a live slice, and munlock does not access it.
+        unsafe {
+            libc::munlock(data.as_ptr().cast(), std::mem::size_of_val(data));
+        }
+    }
+}
+
+/// An LWE secret key, that is wiped from memory when dropped.
+pub struct FheSecretKey(LweSecretKey<Vec<bool>>);
+
+impl FheSecretKey {
+    pub(crate) fn new(key: LweSecretKey<Vec<bool>>) -> Self {
+        lock(key.as_tensor().as_slice());
+        Self(key)
+    }
+}
+
+impl Deref for FheSecretKey {
+    type Target = LweSecretKey<Vec<bool>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for FheSecretKey {
+    fn drop(&mut self) {
+        let bits = self.0.as_mut_tensor().as_mut_slice();
+        bits.zeroize();
+        unlock(bits);
+    }
+}
+
+impl Serialize for FheSecretKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FheSecretKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        LweSecretKey::deserialize(deserializer).map(Self::new)
+    }
+}
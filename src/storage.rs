@@ -0,0 +1,61 @@
+use crate::error::ElisabethError;
+use serde::{de::DeserializeOwned, Serialize};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+use zeroize::Zeroizing;
+
+/// Returns the directory where keys are stored, if the `KEY_DIRECTORY` variable is set.
+pub fn key_directory() -> Option<PathBuf> {
+    env::var_os("KEY_DIRECTORY").map(|dir| PathBuf::from(dir).join("keys"))
+}
+
+/// Reads and deserializes the key stored at `path`.
+/// The serialized key is wiped from memory once deserialized.
+pub fn read_key<K: DeserializeOwned>(path: PathBuf) -> Result<K, ElisabethError> {
+    let serialized = Zeroizing::new(fs::read(&path).map_err(|error| match error.kind() {
+        io::ErrorKind::NotFound => ElisabethError::MissingKey(path.clone()),
+        _ => ElisabethError::Io(error),
+    })?);
+    bincode::deserialize(&serialized).map_err(|source| ElisabethError::CorruptKey { path, source })
+}
+
+/// Serializes and writes `key` at `path`, creating the parent directories if needed.
+/// The serialized key is wiped from memory once written.
+///
+/// The key is first written and synced to a temporary file next to `path`, which is then renamed
+/// over it, so that a crash leaves either the previous content or the new one, never a truncated
+/// file. This matters for the usage counters, which are rewritten at every encryption.
+/// On unix, the file is only readable and writable by its owner, as most keys are secret.
+pub fn write_key<K: Serialize>(path: PathBuf, key: &K) -> Result<(), ElisabethError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let serialized =
+        Zeroizing::new(bincode::serialize(key).map_err(ElisabethError::Serialization)?);
+    let mut temporary = path.clone().into_os_string();
+    temporary.push(".tmp");
+    // a temporary file left by a crash would keep its permissions
+    remove_key(PathBuf::from(&temporary))?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&temporary)?;
+    file.write_all(&serialized)?;
+    file.sync_all()?;
+    fs::rename(temporary, path)?;
+    Ok(())
+}
+
+/// Removes the key stored at `path`, if any.
+pub fn remove_key(path: PathBuf) -> Result<(), ElisabethError> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
@@ -1,67 +1,229 @@
-use crate::{nibble::Nibble, u4};
+use crate::{
+    error::ElisabethError,
+    nibble::{Nibble, Uint, Width},
+    secret,
+    xof::{AesCtr, Shake128, Xof, XofKind},
+};
 use concrete_core::math::random::RandomGenerator;
-use std::cell::{Ref, RefCell};
+use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, ops::Range};
+use zeroize::{Zeroize, Zeroizing};
 
-pub struct SymmetricKey<M: Nibble> {
-    key: Vec<M>,
-    indices: RefCell<Vec<usize>>,
-    key_round: RefCell<Vec<M>>,
-    whitening: Vec<u4>,
-    rng: RandomGenerator,
+/// A public value of 96 bits selecting the keystream used for one message.
+///
+/// The nonce is not secret and should be stored next to the ciphertext, but a given nonce must
+/// never be used twice with the same key: both messages would be encrypted with the same
+/// keystream. It seeds the public randomness along with the 32-bit position of each nibble in the
+/// message, which together fill the 16 bytes of the seed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Nonce(pub [u8; 12]);
+
+impl Nonce {
+    /// Draws a fresh nonce uniformly at random.
+    ///
+    /// Out of `m` random nonces, two are equal with a probability below `m^2 / 2^97`, that is
+    /// `2^-33` for `2^32` messages under the same key. Clients encrypting more messages than that
+    /// under one key should rather number them, and build their nonces from the numbers.
+    #[must_use]
+    pub fn random() -> Self {
+        let mut generator = RandomGenerator::new(None);
+        Self([(); 12].map(|()| generator.random_uniform()))
+    }
 }
 
-impl<M: Nibble> SymmetricKey<M> {
-    pub fn new(key: Vec<M>, n: usize, seed: u128) -> Self {
-        let mut indices = Vec::with_capacity(key.len() as usize);
-        indices.extend(0..key.len());
-        let key_round = key.iter().take(n).cloned().collect::<Vec<M>>();
-        let whitening = vec![u4(0); n];
-        Self {
-            key,
-            indices: RefCell::new(indices),
-            key_round: RefCell::new(key_round),
-            whitening,
-            rng: RandomGenerator::new(Some(seed)),
+/// The public randomness of one keystream nibble: which key nibbles are selected, and with
+/// which nibbles they are whitened.
+#[derive(Clone, Debug)]
+pub struct Round<const BITS: usize = 4> {
+    pub(crate) indices: Vec<usize>,
+    pub(crate) whitening: Vec<Uint<BITS>>,
+}
+
+impl<const BITS: usize> Round<BITS> {
+    /// Draws the public randomness of the `counter`-th nibble of the keystream selected by `nonce`
+    /// with the preset generator `xof`.
+    pub(crate) fn new(
+        xof: XofKind,
+        nonce: Nonce,
+        counter: u64,
+        n: usize,
+        key_size: usize,
+    ) -> Result<Self, ElisabethError> {
+        match xof {
+            XofKind::AesCtr => Self::generate::<AesCtr>(nonce, counter, n, key_size),
+            XofKind::Shake128 => Self::generate::<Shake128>(nonce, counter, n, key_size),
         }
     }
 
-    pub fn random_whitened_subset(&mut self) -> Ref<'_, Vec<M>> {
-        let mut indices = self.indices.borrow_mut();
-        let mut key_round = self.key_round.borrow_mut();
-        let n = self.whitening.len();
-        let key_len = self.key.len();
+    /// Draws the public randomness of the `counter`-th nibble of the keystream selected by `nonce`
+    /// with the generator `G`, for a key of `key_size` nibbles out of which `n` are selected.
+    ///
+    /// The indices are the first `n` entries of a uniformly random permutation of the key,
+    /// obtained with a partial Fisher-Yates shuffle. The way the output of the generator is
+    /// consumed is described in the `xof` module.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NonceLimit` if `counter` does not fit in the 32 bits the seed of the generator
+    /// keeps for it, and `ParameterMismatch` if `n` is larger than `key_size`, or if `key_size` is
+    /// larger than `2^16`, since the indices are drawn from two bytes of the generator.
+    pub fn generate<G: Xof>(
+        nonce: Nonce,
+        counter: u64,
+        n: usize,
+        key_size: usize,
+    ) -> Result<Self, ElisabethError> {
+        if n > key_size || key_size > 1 << 16 {
+            return Err(ElisabethError::ParameterMismatch(format!(
+                "cannot draw {n} indices out of a key of {key_size} nibbles"
+            )));
+        }
+        let mut rng = G::new(
+            nonce,
+            u32::try_from(counter).map_err(|_| ElisabethError::NonceLimit {
+                limit: 1 << 32,
+                end: counter.saturating_add(1),
+            })?,
+        );
 
+        let mut indices = Vec::with_capacity(key_size);
+        indices.extend(0..key_size);
         for i in 0..n {
-            let j = gen_range(&mut self.rng, i, key_len);
+            let j = gen_range(&mut rng, i, key_size);
             indices.swap(i, j);
         }
+        indices.truncate(n);
 
-        for w in self.whitening.iter_mut() {
-            *w = u4(self.rng.random_uniform::<u8>() % (1 << 4));
+        let mut bytes = vec![0; (n * BITS).div_ceil(8)];
+        rng.fill_bytes(&mut bytes);
+        let whitening = (0..n)
+            .map(|i| {
+                // the two bytes the nibble may span, as a big-endian integer
+                let (byte, bit) = (i * BITS / 8, i * BITS % 8);
+                let window = u16::from_be_bytes([bytes[byte], *bytes.get(byte + 1).unwrap_or(&0)]);
+                Uint((window >> (16 - BITS - bit)) as u8 & Uint::<BITS>::MASK)
+            })
+            .collect();
+
+        Ok(Self { indices, whitening })
+    }
+
+    /// Returns the indices of the key nibbles selected at this round.
+    #[must_use]
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    /// Returns the nibbles added to the selected key nibbles.
+    #[must_use]
+    pub fn whitening(&self) -> &[Uint<BITS>] {
+        &self.whitening
+    }
+}
+
+pub struct SymmetricKey<M: Zeroize> {
+    key: Vec<M>,
+    n: usize,
+    xof: XofKind,
+    locked: bool,
+}
+
+impl<M: Zeroize> SymmetricKey<M> {
+    pub fn new(key: Vec<M>, n: usize, xof: XofKind) -> Result<Self, ElisabethError> {
+        if n > key.len() {
+            return Err(ElisabethError::ParameterMismatch(format!(
+                "cannot select {} nibbles per round from a key of {} nibbles",
+                n,
+                key.len()
+            )));
         }
+        Ok(Self {
+            key,
+            n,
+            xof,
+            locked: false,
+        })
+    }
+
+    /// Keeps the nibbles of the key out of swap until it is dropped.
+    /// This is only useful when they hold their value inline, as clear nibbles do: for encrypted
+    /// nibbles, it would only lock the headers of their ciphertexts.
+    pub fn lock(&mut self) {
+        secret::lock(&self.key);
+        self.locked = true;
+    }
+
+    /// Draws the public randomness of the `counter`-th nibble of the keystream selected by `nonce`.
+    /// It only depends on the nonce and the counter, so that any party holding the key can
+    /// regenerate any part of the keystream.
+    pub fn round<const BITS: usize>(
+        &self,
+        nonce: Nonce,
+        counter: u64,
+    ) -> Result<Round<BITS>, ElisabethError> {
+        Round::new(self.xof, nonce, counter, self.n, self.key.len())
+    }
+
+    /// Returns the nibbles of the key.
+    pub fn key(&self) -> &[M] {
+        &self.key
+    }
+
+    /// Returns the number of key nibbles selected at each round.
+    pub const fn round_size(&self) -> usize {
+        self.n
+    }
 
-        key_round
-            .iter_mut()
-            .take(n)
-            .zip(indices.iter().zip(self.whitening.iter()))
-            .for_each(|(key_bit, (&i, w))| {
-                *key_bit = self.key[i].clone();
-                key_bit.add_assign_u4(w);
-            });
-        drop(key_round);
-
-        self.key_round.borrow()
+    /// Returns the nibbles `range` of the whitened subset of the key selected by `round`.
+    /// They are wiped from memory when dropped, as the key itself.
+    pub fn whitened_subset<const BITS: usize>(
+        &self,
+        round: &Round<BITS>,
+        range: Range<usize>,
+    ) -> Zeroizing<Vec<M>>
+    where
+        M: Nibble<BITS>,
+        Uint<BITS>: Width,
+    {
+        Zeroizing::new(
+            round.indices[range.clone()]
+                .iter()
+                .zip(round.whitening[range].iter())
+                .map(|(&i, w)| self.key[i].add_u4(w))
+                .collect(),
+        )
     }
 }
 
-fn gen_range(rng: &mut RandomGenerator, min: usize, max: usize) -> usize {
-    if min > max {
-        panic!("`min` of range must be less than or equal to `max`");
+impl<M: Zeroize> Drop for SymmetricKey<M> {
+    fn drop(&mut self) {
+        // the nibbles are wiped in place, so that the locked range is still the one of the key
+        for nibble in &mut self.key {
+            nibble.zeroize();
+        }
+        if self.locked {
+            secret::unlock(&self.key);
+        }
+        self.key.zeroize();
     }
-    let bit_len = ((max - min) as f64).log2().floor() as usize;
-    let mut a = (min as u32 + rng.random_uniform_n_lsb::<u32>(bit_len)) as usize;
-    while a >= max {
-        a = (min as u32 + rng.random_uniform_n_lsb::<u32>(bit_len)) as usize;
+}
+
+/// Draws an integer uniformly at random in `min..max`.
+///
+/// Just enough bits of two random bytes to cover the range are kept, and the draw is rejected
+/// until it falls into the range, so that less than two draws are needed on average.
+/// The caller checks that the range is not empty and fits in two bytes: past two bytes, the draws
+/// would silently stop covering the range.
+fn gen_range<G: Xof>(rng: &mut G, min: usize, max: usize) -> usize {
+    debug_assert!(min < max && max - min <= 1 << 16);
+    let range = max - min;
+    let mask = (1 << (usize::BITS - (range - 1).leading_zeros())) - 1;
+    let mut bytes = [0; 2];
+    loop {
+        rng.fill_bytes(&mut bytes);
+        let a = usize::from(u16::from_be_bytes(bytes)) & mask;
+        if a < range {
+            return min + a;
+        }
     }
-    a
 }
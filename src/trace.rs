@@ -0,0 +1,325 @@
+//! Extraction of the circuit that computes the keystream.
+//!
+//! A `Traced` nibble holds no value, but the node of a circuit that computes it. Every operation
+//! applied to it appends a node to the circuit, so that evaluating the filter on a round of traced
+//! key nibbles records the exact sequence of additions, keyswitchings and bootstrappings an `LWE`
+//! evaluation performs. `SystemParameters::circuit` does so for one nibble of keystream.
+//!
+//! The circuit can be exported to JSON, for other FHE compilers and schedulers, and to Graphviz
+//! DOT, to inspect its structure.
+
+use crate::{error::ElisabethError, nibble::Nibble, u4, SystemParameters};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write,
+    mem,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+use zeroize::Zeroize;
+
+/// A node of the circuit, which refers to its inputs by their index among the nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Node {
+    /// The `index`-th nibble of the whitened key round.
+    Input {
+        index: usize,
+    },
+    /// A clear nibble.
+    Constant {
+        value: u8,
+    },
+    Add {
+        lhs: usize,
+        rhs: usize,
+    },
+    /// The addition of a clear nibble.
+    AddU4 {
+        input: usize,
+        value: u8,
+    },
+    Negate {
+        input: usize,
+    },
+    Keyswitch {
+        input: usize,
+    },
+    /// A bootstrapping evaluating the `table`-th S-box of the circuit.
+    Sbox {
+        input: usize,
+        table: usize,
+    },
+}
+
+/// The circuit computing one nibble of keystream from a whitened key round.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Circuit {
+    /// The number of nibbles of the key round.
+    pub inputs: usize,
+    /// The S-boxes, `S(0)` first. They are on 4 bits, as only nibbles of 4 bits are traced.
+    pub tables: Vec<[u8; 16]>,
+    /// The nodes, each after its inputs.
+    pub nodes: Vec<Node>,
+    /// The node holding the keystream nibble.
+    pub output: usize,
+}
+
+impl Circuit {
+    /// Returns the number of nodes matching the predicate.
+    pub fn count(&self, predicate: impl Fn(&Node) -> bool) -> usize {
+        self.nodes.iter().filter(|node| predicate(node)).count()
+    }
+
+    /// Evaluates the circuit on a clear key round, keyswitchings being the identity.
+    ///
+    /// # Errors
+    ///
+    /// Returns `LengthMismatch` if the key round does not have `inputs` nibbles, and
+    /// `InvalidCircuit` if a node reads a node that is not before it, an input or an S-box the
+    /// circuit does not have, or adds a constant that is not a nibble.
+    pub fn evaluate(&self, key_round: &[u4]) -> Result<u4, ElisabethError> {
+        if key_round.len() != self.inputs {
+            return Err(ElisabethError::LengthMismatch {
+                expected: self.inputs,
+                found: key_round.len(),
+            });
+        }
+        let invalid = ElisabethError::InvalidCircuit;
+
+        // every value is reduced modulo 16, so that the operations below never overflow
+        let mut values: Vec<u8> = Vec::with_capacity(self.nodes.len());
+        for (i, node) in self.nodes.iter().enumerate() {
+            // the values computed so far are those of the nodes before this one
+            let read = |j: usize| match values.get(j) {
+                Some(&value) => Ok(value),
+                None => Err(invalid(format!(
+                    "node {i} reads node {j}, which is not before it"
+                ))),
+            };
+            let nibble = |value: u8| {
+                if value < 16 {
+                    Ok(value)
+                } else {
+                    Err(invalid(format!(
+                        "node {i} adds {value}, which is not a nibble"
+                    )))
+                }
+            };
+            let value = match *node {
+                Node::Input { index } => match key_round.get(index) {
+                    Some(u) => u.0,
+                    None => return Err(invalid(format!("node {i} reads the input {index}"))),
+                },
+                Node::Constant { value } => nibble(value)?,
+                Node::Add { lhs, rhs } => read(lhs)? + read(rhs)?,
+                Node::AddU4 { input, value } => read(input)? + nibble(value)?,
+                Node::Negate { input } => 16 - read(input)?,
+                Node::Keyswitch { input } => read(input)?,
+                Node::Sbox { input, table } => match self.tables.get(table) {
+                    Some(table) => table[usize::from(read(input)?)],
+                    None => return Err(invalid(format!("node {i} applies the S-box {table}"))),
+                },
+            };
+            values.push(value % 16);
+        }
+        match values.get(self.output) {
+            Some(&value) => Ok(u4(value)),
+            None => Err(invalid(format!("the output is the node {}", self.output))),
+        }
+    }
+
+    /// Returns the circuit in JSON, with nodes of the form `{"op": "add", "lhs": 3, "rhs": 4}`.
+    // the `expect` can not fail, a circuit only holds integers
+    #[allow(clippy::missing_panics_doc)]
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a circuit is always serializable")
+    }
+
+    /// Returns the circuit as a Graphviz directed graph, node `i` being named `n<i>`.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph keystream {\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let (label, shape, inputs) = match *node {
+                Node::Input { index } => (format!("k{index}"), "box", vec![]),
+                Node::Constant { value } => (value.to_string(), "box", vec![]),
+                Node::Add { lhs, rhs } => ("+".to_owned(), "circle", vec![lhs, rhs]),
+                Node::AddU4 { input, value } => (format!("+{value}"), "circle", vec![input]),
+                Node::Negate { input } => ("-".to_owned(), "circle", vec![input]),
+                Node::Keyswitch { input } => ("KS".to_owned(), "diamond", vec![input]),
+                Node::Sbox { input, table } => (format!("S{table}"), "box", vec![input]),
+            };
+            let peripheries = if i == self.output { 2 } else { 1 };
+            // writing to a string never fails
+            let _ = writeln!(
+                dot,
+                "    n{i} [label=\"{label}\", shape={shape}, peripheries={peripheries}];"
+            );
+            for input in inputs {
+                let _ = writeln!(dot, "    n{input} -> n{i};");
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[derive(Default)]
+struct Recording {
+    inputs: usize,
+    tables: Vec<[u8; 16]>,
+    nodes: Vec<Node>,
+}
+
+/// Records the circuit the traced nibbles are computed with. Clones record into the same circuit.
+#[derive(Clone, Default)]
+pub struct Tracer(Arc<Mutex<Recording>>);
+
+impl Tracer {
+    fn recording(&self) -> MutexGuard<'_, Recording> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn push(&self, node: Node) -> Traced {
+        let mut recording = self.recording();
+        recording.nodes.push(node);
+        Traced {
+            node: recording.nodes.len() - 1,
+            tracer: Some(self.clone()),
+        }
+    }
+
+    /// Returns the circuit recorded so far, computing `output`, and starts a new one.
+    #[must_use]
+    pub fn finish(&self, output: &Traced) -> Circuit {
+        let recording = mem::take(&mut *self.recording());
+        Circuit {
+            inputs: recording.inputs,
+            tables: recording.tables,
+            nodes: recording.nodes,
+            output: output.node,
+        }
+    }
+}
+
+/// A nibble of 4 bits standing for a node of a circuit. Wider nibbles are not traced, as the FHE
+/// parameters only support nibbles of up to 4 bits.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Traced {
+    node: usize,
+    /// A deserialized nibble no longer records its operations.
+    #[serde(skip)]
+    tracer: Option<Tracer>,
+}
+
+impl Traced {
+    /// Returns the index of the node holding this nibble.
+    #[must_use]
+    pub const fn node(&self) -> usize {
+        self.node
+    }
+
+    fn push(&self, node: Node) -> Self {
+        self.tracer
+            .as_ref()
+            .map_or_else(|| self.clone(), |tracer| tracer.push(node))
+    }
+}
+
+/// A traced nibble holds no secret.
+impl Zeroize for Traced {
+    fn zeroize(&mut self) {}
+}
+
+impl Nibble for Traced {
+    type EncryptionContext = Tracer;
+    type EvaluationContext = Tracer;
+
+    fn trivial(u: u4, tracer: &Tracer) -> Result<Self, ElisabethError> {
+        Ok(tracer.push(Node::Constant { value: u.0 }))
+    }
+
+    /// The value is not recorded: encrypted nibbles are the inputs of the circuit.
+    fn encrypt(_u: u4, tracer: &Tracer) -> Result<Self, ElisabethError> {
+        let index = {
+            let mut recording = tracer.recording();
+            recording.inputs += 1;
+            recording.inputs - 1
+        };
+        Ok(tracer.push(Node::Input { index }))
+    }
+
+    /// The index of the S-box among the tables of the circuit.
+    type Sbox = usize;
+
+    fn sbox(table: &[u4], tracer: &Tracer) -> Result<Self::Sbox, ElisabethError> {
+        let mut entries = [0; 16];
+        for (entry, u) in entries.iter_mut().zip(table) {
+            *entry = u.0;
+        }
+        let mut recording = tracer.recording();
+        recording.tables.push(entries);
+        Ok(recording.tables.len() - 1)
+    }
+
+    fn apply_sbox(&self, table: &usize, _tracer: &Tracer) -> Result<Self, ElisabethError> {
+        Ok(self.push(Node::Sbox {
+            input: self.node,
+            table: *table,
+        }))
+    }
+
+    fn negate(&mut self) {
+        *self = self.push(Node::Negate { input: self.node });
+    }
+
+    fn keyswitch(&mut self, _tracer: &Tracer) -> Result<(), ElisabethError> {
+        *self = self.push(Node::Keyswitch { input: self.node });
+        Ok(())
+    }
+
+    fn add(&self, rhs: &Self) -> Self {
+        self.push(Node::Add {
+            lhs: self.node,
+            rhs: rhs.node,
+        })
+    }
+
+    fn add_u4(&self, rhs: &u4) -> Self {
+        self.push(Node::AddU4 {
+            input: self.node,
+            value: rhs.0,
+        })
+    }
+
+    fn add_assign(&mut self, rhs: &Self) {
+        *self = self.add(rhs);
+    }
+
+    fn add_assign_u4(&mut self, rhs: &u4) {
+        *self = self.add_u4(rhs);
+    }
+}
+
+impl SystemParameters {
+    /// Returns the circuit computing a nibble of keystream from the whitened key round, as an
+    /// `LWE` evaluation of the filter of this preset performs it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the filter of the preset can not be evaluated.
+    pub fn circuit(&self) -> Result<Circuit, ElisabethError> {
+        let parameters = self.parameters();
+        let tracer = Tracer::default();
+        let key_round = (0..parameters.n)
+            .map(|_| Traced::encrypt(u4(0), &tracer))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let output =
+            parameters
+                .filter
+                .call(&key_round, &parameters.filter.sboxes(&tracer)?, &tracer)?;
+        Ok(tracer.finish(&output))
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{
+    backend::{Concrete, FheBackend},
+    encrypter::parameters::ParameterSet,
+    error::ElisabethError,
+    nibble::{Uint, Width},
+    symmetric_key::Nonce,
+    Encrypter, LWE,
+};
+
+/// The server side of Elisabeth, used to transcrypt symmetric ciphertexts into LWE ciphertexts.
+///
+/// It is built from public material only: the symmetric key encrypted by the client and the
+/// public key. No LWE secret key can be given to it. Its outputs are ciphertexts of the FHE
+/// backend `B`.
+pub struct Transcipherer<B: FheBackend = Concrete, const BITS: usize = 4>(
+    Encrypter<LWE<B, BITS>, BITS>,
+)
+where
+    Uint<BITS>: Width;
+
+impl<B: FheBackend, const BITS: usize> Transcipherer<B, BITS>
+where
+    Uint<BITS>: Width,
+{
+    /// Builds a transcipherer from the encrypted symmetric key exported by the client with
+    /// `Encrypter::export_encrypted_key`, and the public key matching the secret key it was
+    /// encrypted under.
+    ///
+    /// # Errors
+    ///
+    /// Fails as `Encrypter::from_encrypted_key`.
+    pub fn new(
+        params: &impl ParameterSet<BITS>,
+        encrypted_key: Vec<LWE<B, BITS>>,
+        public_key: B::PublicKey,
+    ) -> Result<Self, ElisabethError> {
+        Encrypter::from_encrypted_key(params, encrypted_key, public_key).map(Self)
+    }
+
+    /// Transcrypts the given symmetric ciphertext, encrypted under `nonce`, into LWE ciphertexts.
+    ///
+    /// # Errors
+    ///
+    /// Fails as `transcipher_at`.
+    pub fn transcipher(
+        &self,
+        nonce: Nonce,
+        ciphertext: &[Uint<BITS>],
+    ) -> Result<Vec<LWE<B, BITS>>, ElisabethError> {
+        self.transcipher_at(nonce, 0, ciphertext)
+    }
+
+    /// Transcrypts the given symmetric ciphertext, assuming its first nibble is the `offset`-th
+    /// one of the message encrypted under `nonce`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `NonceLimit` if the ciphertext goes beyond the keystream of a nonce, or the error
+    /// of a homomorphic operation, such as a ciphertext under an unexpected key.
+    pub fn transcipher_at(
+        &self,
+        nonce: Nonce,
+        offset: u64,
+        ciphertext: &[Uint<BITS>],
+    ) -> Result<Vec<LWE<B, BITS>>, ElisabethError> {
+        self.0.decrypt_to_vec(nonce, offset, ciphertext)
+    }
+}
@@ -0,0 +1,105 @@
+//! The generators of the public randomness of Elisabeth.
+//!
+//! The construction below is the one of this crate. It is not taken from the Elisabeth paper nor
+//! from its reference implementation, so that keystreams only match those of implementations that
+//! follow this description, such as `tests/test_vectors.py`.
+//!
+//! At each round, the public randomness is read from a fresh generator seeded with the 16 bytes
+//! `nonce || counter`: the 12 bytes of the nonce, followed by the counter encoded as a big-endian
+//! 32-bit integer. The bytes it outputs are then consumed as follows, so that any implementation
+//! can reproduce the keystream bit for bit:
+//!
+//! 1. For `i` in `0..n`, an index `j` is drawn uniformly in `i..key_size` and the entries `i` and
+//!    `j` of the identity permutation of `0..key_size` are swapped. To draw `j`, two bytes are
+//!    read as a big-endian integer, reduced to their `b` least significant bits where `b` is the
+//!    bit length of `key_size - i - 1`, and the draw is rejected and repeated until the result is
+//!    smaller than `key_size - i`. The selected key nibbles are the first `n` entries of the
+//!    permutation.
+//! 2. `ceil(n * BITS / 8)` bytes are read, for nibbles of `BITS` bits, and split into `n`
+//!    whitening nibbles of `BITS` bits, the most significant bits of the first byte first. With 4
+//!    bits, each byte gives two nibbles: its most significant half, then its least significant one.
+
+use crate::symmetric_key::Nonce;
+use aes::{
+    cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit},
+    Aes128,
+};
+use sha3::{
+    digest::{ExtendableOutput, Update, XofReader},
+    Shake128Reader,
+};
+
+/// A generator of public randomness, seeded by a nonce and a keystream counter.
+pub trait Xof {
+    /// Creates the generator of the `counter`-th round of the keystream selected by `nonce`.
+    fn new(nonce: Nonce, counter: u32) -> Self;
+
+    /// Fills `bytes` with the next output bytes of the generator.
+    fn fill_bytes(&mut self, bytes: &mut [u8]);
+}
+
+/// The preset generators, as selected by the `SystemParameters`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XofKind {
+    AesCtr,
+    Shake128,
+}
+
+/// Returns the 16 bytes seeding the generator of a round.
+fn seed(nonce: Nonce, counter: u32) -> [u8; 16] {
+    let mut seed = [0; 16];
+    seed[..12].copy_from_slice(&nonce.0);
+    seed[12..].copy_from_slice(&counter.to_be_bytes());
+    seed
+}
+
+/// AES-128 in counter mode.
+///
+/// The seed is used as the AES key, and the output is the encryption of the successive
+/// counter blocks 0, 1, 2, ..., encoded as big-endian 128-bit integers.
+pub struct AesCtr {
+    cipher: Aes128,
+    block_counter: u128,
+    block: [u8; 16],
+    position: usize,
+}
+
+impl Xof for AesCtr {
+    fn new(nonce: Nonce, counter: u32) -> Self {
+        Self {
+            cipher: Aes128::new(&GenericArray::from(seed(nonce, counter))),
+            block_counter: 0,
+            block: [0; 16],
+            position: 16,
+        }
+    }
+
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        for byte in bytes {
+            if self.position == 16 {
+                let mut block = GenericArray::from(self.block_counter.to_be_bytes());
+                self.cipher.encrypt_block(&mut block);
+                self.block.copy_from_slice(&block);
+                self.block_counter += 1;
+                self.position = 0;
+            }
+            *byte = self.block[self.position];
+            self.position += 1;
+        }
+    }
+}
+
+/// SHAKE128, absorbing the seed and squeezing the output.
+pub struct Shake128(Shake128Reader);
+
+impl Xof for Shake128 {
+    fn new(nonce: Nonce, counter: u32) -> Self {
+        let mut hasher = sha3::Shake128::default();
+        hasher.update(&seed(nonce, counter));
+        Self(hasher.finalize_xof())
+    }
+
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        self.0.read(bytes);
+    }
+}
@@ -0,0 +1,62 @@
+use elisabeth::{
+    analysis::{analyze_filter_block, analyze_sbox, Properties, SboxReport},
+    u4, FilterSpec,
+};
+
+fn sbox(table: [u8; 16]) -> [u4; 16] {
+    table.map(u4)
+}
+
+fn main() {
+    // the S-box of PRESENT, whose properties are well known
+    let present = sbox([
+        0xC, 0x5, 0x6, 0xB, 0x9, 0x0, 0xA, 0xD, 0x3, 0xE, 0xF, 0x8, 0x4, 0x7, 0x1, 0x2,
+    ]);
+    let report = analyze_sbox(&present);
+    assert_eq!(report.properties.differential_uniformity, 4);
+    assert_eq!(report.properties.linearity, 8);
+    assert_eq!(report.properties.algebraic_degree, 3);
+    assert_eq!(report.fixed_points, 0);
+    assert!(report.bijective);
+    assert!(!report.negacyclic);
+
+    // the identity is as weak as it gets
+    let identity = sbox([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+    assert_eq!(
+        analyze_sbox(&identity),
+        SboxReport {
+            properties: Properties {
+                differential_uniformity: 16,
+                additive_differential_uniformity: 16,
+                linearity: 16,
+                algebraic_degree: 1,
+                balanced: true,
+            },
+            fixed_points: 16,
+            bijective: true,
+            negacyclic: false,
+        }
+    );
+
+    // the S-boxes of Elisabeth-4 are negacyclic, so that S(x) = 0 implies S(x + 8) = 0, and none
+    // of them can be a permutation
+    let spec = FilterSpec::elisabeth4();
+    for sbox in spec.first_layer.iter().chain(spec.second_layer.iter()) {
+        let report = analyze_sbox(sbox);
+        assert!(report.negacyclic);
+        assert!(!report.bijective);
+    }
+
+    // the last nibble of a block is added to its output, which balances it but lets any difference
+    // on that nibble go through with certainty
+    let block = analyze_filter_block(&spec).unwrap();
+    assert!(block.properties.balanced);
+    assert_eq!(block.differential_uniformity_by_nibble[4], 1 << 20);
+    assert!(block.differential_uniformity_by_nibble[..4]
+        .iter()
+        .all(|&du| du < 1 << 20));
+
+    let mut wide = spec;
+    wide.block_width = 7;
+    assert!(analyze_filter_block(&wide).is_err());
+}
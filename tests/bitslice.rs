@@ -0,0 +1,84 @@
+use elisabeth::{
+    u4, xof::XofKind, CustomParameters, Encrypter, FilterSpec, Nonce, SystemParameters,
+};
+
+fn add(a: u8, b: u8) -> u8 {
+    (a + b) % 16
+}
+
+/// Evaluates the filter one nibble at a time, straight from its description.
+fn scalar_keystream(
+    params: &SystemParameters,
+    spec: &FilterSpec,
+    key: &[u4],
+    nonce: Nonce,
+    offset: u64,
+    len: usize,
+) -> Vec<u8> {
+    (offset..offset + len as u64)
+        .map(|counter| {
+            let round = params.round(nonce, counter).unwrap();
+            let x = round
+                .indices()
+                .iter()
+                .zip(round.whitening())
+                .map(|(&i, w)| add(key[i].0, w.0))
+                .collect::<Vec<_>>();
+
+            x.chunks(spec.block_width).fold(0, |acc, block| {
+                let lanes = spec.block_width - 1;
+                let y = (0..lanes)
+                    .map(|i| {
+                        spec.first_layer[i][add(block[i], block[spec.first_wiring[i]]) as usize].0
+                    })
+                    .collect::<Vec<_>>();
+                let output = (0..lanes).fold(block[lanes], |acc, i| {
+                    let input = spec.second_wiring[i]
+                        .iter()
+                        .fold(block[i], |sum, &j| add(sum, y[j]));
+                    add(acc, spec.second_layer[i][input as usize].0)
+                });
+                add(acc, output)
+            })
+        })
+        .collect()
+}
+
+fn check(params: &SystemParameters, spec: &FilterSpec) {
+    let key = params.generate_symmetric_key();
+    let encrypter = Encrypter::from_symmetric_key(params, key.clone()).unwrap();
+    let nonce = Nonce::random();
+
+    // lengths and offsets that do not fall on batches of 64 nibbles
+    for &(offset, len) in &[(0, 1), (0, 64), (3, 200), (1000, 129)] {
+        let zeros = vec![u4(0); len];
+        let mut keystream = vec![u4(0); len];
+        encrypter
+            .encrypt_at(nonce, offset, &mut keystream, &zeros)
+            .unwrap();
+
+        let expected = scalar_keystream(params, spec, &key, nonce, offset, len);
+        assert!(keystream
+            .iter()
+            .zip(expected.iter())
+            .all(|(a, &b)| a.0 == b));
+    }
+}
+
+fn main() {
+    let elisabeth4 = FilterSpec::elisabeth4();
+    check(&SystemParameters::n60, &elisabeth4);
+    check(&SystemParameters::n60_shake, &elisabeth4);
+
+    let spec = FilterSpec {
+        block_width: 4,
+        first_layer: elisabeth4.first_layer[1..].to_vec(),
+        second_layer: elisabeth4.second_layer[1..].to_vec(),
+        first_wiring: vec![1, 2, 0],
+        second_wiring: vec![vec![2], vec![0, 1, 2], vec![1, 0]],
+    };
+    let params = SystemParameters::Custom(
+        CustomParameters::new(40, 128, spec.clone(), XofKind::AesCtr).unwrap(),
+    );
+    check(&params, &spec);
+}
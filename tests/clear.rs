@@ -1,13 +1,13 @@
 use concrete_core::math::random::RandomGenerator;
-use elisabeth::{u4, Encrypter, SystemParameters};
+use elisabeth::{u4, ElisabethError, Encrypter, Nonce, SystemParameters};
 use std::env;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     let nb_nibble = args[1].parse().unwrap();
 
-    let (mut encryptor, mut decryptor) =
-        Encrypter::<u4>::new::<u4>(&SystemParameters::n60, None, None, None);
+    let (encryptor, decryptor) =
+        Encrypter::<u4>::new::<u4>(&SystemParameters::n60, (&(), &()), ((), ())).unwrap();
 
     // message
     let mut generator = RandomGenerator::new(None);
@@ -21,10 +21,66 @@ fn main() {
     let mut ciphertext = vec![u4(0); nb_nibble];
     let mut decryption = vec![u4(0); nb_nibble];
 
-    encryptor.encrypt(&mut ciphertext, &message);
-    decryptor.decrypt(&mut decryption, &ciphertext);
+    let nonce = Nonce::random();
+    encryptor.encrypt(nonce, &mut ciphertext, &message).unwrap();
+    decryptor
+        .decrypt(nonce, &mut decryption, &ciphertext)
+        .unwrap();
 
     for (a, b) in message.iter().zip(decryption.iter()) {
         assert_eq!(a.0, b.0);
     }
+
+    // random access: decrypt the second half without going through the first one
+    let offset = nb_nibble / 2;
+    let mut partial_decryption = vec![u4(0); nb_nibble - offset];
+    decryptor
+        .decrypt_at(
+            nonce,
+            offset as u64,
+            &mut partial_decryption,
+            &ciphertext[offset..],
+        )
+        .unwrap();
+
+    for (a, b) in message[offset..].iter().zip(partial_decryption.iter()) {
+        assert_eq!(a.0, b.0);
+    }
+
+    // a buffer of the wrong length is rejected instead of being silently truncated
+    assert!(matches!(
+        decryptor.decrypt(nonce, &mut decryption[1..], &ciphertext),
+        Err(ElisabethError::LengthMismatch { .. })
+    ));
+
+    // the keystream of a nonce is bounded
+    let limit = SystemParameters::n60.max_nibbles_per_nonce();
+    assert!(matches!(
+        decryptor.decrypt_at(nonce, limit - 1, &mut decryption, &ciphertext),
+        Err(ElisabethError::NonceLimit { .. })
+    ));
+
+    // so is the data encrypted under a key, and a refused message does not consume anything
+    let mut encryptor = encryptor;
+    encryptor.limit_nibbles(2 * nb_nibble as u64 - 1);
+    let remaining = encryptor.remaining_nibbles();
+    assert!(matches!(
+        encryptor.encrypt(Nonce::random(), &mut ciphertext, &message),
+        Err(ElisabethError::KeyLimit { .. })
+    ));
+    assert_eq!(encryptor.remaining_nibbles(), remaining);
+
+    // until a new key is drawn
+    encryptor.rekey(&SystemParameters::n60).unwrap();
+    encryptor
+        .encrypt(Nonce::random(), &mut ciphertext, &message)
+        .unwrap();
+
+    // which replaces the saved client key, while the saved server key no longer matches it
+    if env::var_os("KEY_DIRECTORY").is_some() {
+        assert!(matches!(
+            Encrypter::<u4>::new::<u4>(&SystemParameters::n60, (&(), &()), ((), ())),
+            Err(ElisabethError::MissingKey(_))
+        ));
+    }
 }
@@ -0,0 +1,93 @@
+use concrete_core::math::random::RandomGenerator;
+use elisabeth::{u4, Encrypter, Filter, FilterSpec, Nonce, SystemParameters};
+use std::{env, time::Instant};
+
+/// The value of the t statistic above which dudect considers that the timing definitely depends on
+/// the class of the input.
+const THRESHOLD: f64 = 10.;
+
+/// The proportion of the slowest measurements that are discarded, as they are mostly due to
+/// interruptions.
+const CROPPED: f64 = 0.1;
+
+/// Welch's t statistic of two sets of measurements.
+fn welch_t(a: &[f64], b: &[f64]) -> f64 {
+    let stats = |x: &[f64]| {
+        let n = x.len() as f64;
+        let mean = x.iter().sum::<f64>() / n;
+        let variance = x.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.);
+        (mean, variance / n)
+    };
+    let ((mean_a, var_a), (mean_b, var_b)) = (stats(a), stats(b));
+    (mean_a - mean_b) / (var_a + var_b).sqrt()
+}
+
+/// Times `run` on inputs of a fixed class, built by `prepare(0)`, and of a random class, built by
+/// `prepare(1)`, in a random order. All the inputs are prepared before measuring, so that both
+/// classes go through the same steps. Returns the t statistic of the two classes.
+fn dudect<I>(measurements: usize, prepare: impl Fn(usize) -> I, run: impl Fn(&I)) -> f64 {
+    let mut generator = RandomGenerator::new(None);
+    let inputs = (0..measurements)
+        .map(|_| {
+            let class = generator.random_uniform::<u8>() as usize & 1;
+            (class, prepare(class))
+        })
+        .collect::<Vec<_>>();
+
+    let mut timings = [Vec::new(), Vec::new()];
+    for (class, input) in &inputs {
+        let start = Instant::now();
+        run(input);
+        timings[*class].push(start.elapsed().as_nanos() as f64);
+    }
+
+    let mut all = timings.concat();
+    all.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let cutoff = all[((1. - CROPPED) * all.len() as f64) as usize - 1];
+    let [fixed, random] =
+        timings.map(|t| t.into_iter().filter(|&v| v <= cutoff).collect::<Vec<_>>());
+    welch_t(&fixed, &random)
+}
+
+/// A dudect-style test of the clear evaluation of the filter: its timing must not depend on the
+/// key, whether the filter is evaluated one nibble at a time on a whitened key round or through the
+/// bitsliced encryption.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let measurements = args.get(1).map_or(100_000, |n| n.parse().unwrap());
+    let params = SystemParameters::n60;
+
+    // the S-boxes are prepared once, so that only the evaluation of the filter is timed
+    let filter = Filter::new(FilterSpec::elisabeth4()).unwrap();
+    let sboxes = filter.sboxes(&()).unwrap();
+    let t = dudect(
+        measurements,
+        |class| {
+            let mut generator = RandomGenerator::new(None);
+            (0..60)
+                .map(|_| u4(generator.random_uniform_n_lsb::<u8>(4) * class as u8))
+                .collect::<Vec<_>>()
+        },
+        |key_round| {
+            filter.call(key_round, &sboxes, &()).unwrap();
+        },
+    );
+    println!("filter: t = {:.2}", t);
+    assert!(t.abs() < THRESHOLD);
+
+    let message = vec![u4(0); 64];
+    let nonce = Nonce::random();
+    let t = dudect(
+        measurements / 10,
+        |class| {
+            let key = [vec![u4(0); 256], params.generate_symmetric_key()][class].clone();
+            Encrypter::from_symmetric_key(&params, key).unwrap()
+        },
+        |encrypter| {
+            let mut ciphertext = vec![u4(0); 64];
+            encrypter.encrypt(nonce, &mut ciphertext, &message).unwrap();
+        },
+    );
+    println!("encryption: t = {:.2}", t);
+    assert!(t.abs() < THRESHOLD);
+}
@@ -0,0 +1,56 @@
+use elisabeth::{
+    cost::{Calibration, Cost},
+    SystemParameters,
+};
+use std::time::Duration;
+
+fn main() {
+    // 12 blocks of 5 nibbles, each with 4 lanes of 2 S-boxes
+    let cost = SystemParameters::n60.cost();
+    #[cfg(not(feature = "single_key"))]
+    let expected = Cost {
+        bootstraps: 96,
+        keyswitches: 48,
+        inverse_keyswitches: 12,
+        additions: 203,
+        negations: 1,
+        plaintext_additions: 61,
+        bootstrap_depth: 2,
+        keyswitch_depth: 1,
+    };
+    #[cfg(feature = "single_key")]
+    let expected = Cost {
+        bootstraps: 96,
+        keyswitches: 60,
+        inverse_keyswitches: 0,
+        additions: 203,
+        negations: 1,
+        plaintext_additions: 61,
+        bootstrap_depth: 2,
+        keyswitch_depth: 2,
+    };
+    assert_eq!(cost, expected);
+
+    let calibration = Calibration {
+        bootstrap: Duration::from_millis(20),
+        keyswitch: Duration::from_millis(2),
+        inverse_keyswitch: Duration::from_millis(1),
+        addition: Duration::from_micros(1),
+        negation: Duration::from_micros(1),
+        plaintext_addition: Duration::from_nanos(10),
+    };
+
+    // on a single core, the nibbles are transciphered one after the other
+    let single = SystemParameters::n60.estimate(&calibration, 100, 1);
+    assert_eq!(single.time, cost.time(&calibration) * 100);
+
+    // the 12 blocks of 8 nibbles are spread over 16 cores, 6 per core
+    let parallel = SystemParameters::n60.estimate(&calibration, 8, 16);
+    assert!(parallel.time < cost.time(&calibration));
+    assert!(parallel.time > cost.time(&calibration) / 2);
+
+    // the public key does not depend on the message, but the output does
+    let longer = SystemParameters::n60.estimate(&calibration, 200, 1);
+    assert!(longer.memory > single.memory);
+    assert!(single.memory > 100 << 20);
+}
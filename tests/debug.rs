@@ -0,0 +1,103 @@
+use concrete_core::math::random::RandomGenerator;
+use elisabeth::{
+    debug::{DebugContext, DebugKey, DebugNibble, Operation},
+    u4, Encrypter, FheSecretKey, FilterSpec, Nibble, Nonce, SystemParameters,
+};
+use std::env;
+
+/// Secret keys can not be cloned, so that every copy is explicit.
+fn duplicate(key: &FheSecretKey) -> FheSecretKey {
+    bincode::deserialize(&bincode::serialize(key).unwrap()).unwrap()
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let nb_nibble = args[1].parse().unwrap();
+
+    // the faulty key records the divergence of the injected fault only
+    #[cfg(not(feature = "single_key"))]
+    let (key, faulty_key, public_key) = {
+        let (lwe_key, sk_out, pk) = SystemParameters::n60.generate_fhe_keys().unwrap();
+        let faulty_key = DebugKey::new((duplicate(&lwe_key.0), lwe_key.1), duplicate(&sk_out));
+        (DebugKey::new(lwe_key, sk_out), faulty_key, pk)
+    };
+    #[cfg(feature = "single_key")]
+    let (key, faulty_key, public_key) = {
+        let (lwe_key, pk) = SystemParameters::n60.generate_fhe_keys().unwrap();
+        let faulty_key = DebugKey::new((duplicate(&lwe_key.0), lwe_key.1));
+        (DebugKey::new(lwe_key), faulty_key, pk)
+    };
+    let faulty_context = DebugContext {
+        public_key: public_key.clone(),
+        key: faulty_key.clone(),
+    };
+
+    let (encrypter, debugger) = Encrypter::<u4>::new::<DebugNibble>(
+        &SystemParameters::n60,
+        (&(), &key),
+        (
+            (),
+            DebugContext {
+                public_key,
+                key: key.clone(),
+            },
+        ),
+    )
+    .unwrap();
+
+    let mut generator = RandomGenerator::new(None);
+    let message = generator
+        .random_uniform_n_lsb_tensor::<u8>(nb_nibble, 4)
+        .into_container()
+        .iter()
+        .map(|f| u4(*f))
+        .collect::<Vec<u4>>();
+    let mut ciphertext = vec![u4(0); nb_nibble];
+    let nonce = Nonce::random();
+    encrypter.encrypt(nonce, &mut ciphertext, &message).unwrap();
+
+    let transciphered = debugger.transcipher(nonce, &ciphertext).unwrap();
+
+    // the shadows go through the same operations in the clear
+    for (nibble, m) in transciphered.iter().zip(message.iter()) {
+        assert_eq!(nibble.shadow().0, m.0);
+    }
+
+    println!("{} operations checked.", key.checked());
+    match key.divergence() {
+        None => println!("Every ciphertext decrypted to its shadow."),
+        Some(divergence) => println!(
+            "Operation {} ({:?}) gave {} instead of {}, with a noise of 2^{}.",
+            divergence.index,
+            divergence.operation,
+            divergence.decrypted.0,
+            divergence.expected.0,
+            divergence.log2_noise()
+        ),
+    }
+
+    // a fault injected in a bootstrapping: its lookup table evaluates an S-box, while the shadow
+    // goes through its opposite, which differs wherever the S-box is neither 0 nor 8
+    let table = FilterSpec::elisabeth4().first_layer[0];
+    let opposite = table.map(|u| u4((16 - u.0) % 16));
+    let x = (0..16).find(|&x| !table[x].0.is_multiple_of(8)).unwrap();
+    let (lut, _) = DebugNibble::sbox(&table, &faulty_context).unwrap();
+    let (_, shadow) = DebugNibble::sbox(&opposite, &faulty_context).unwrap();
+    let nibble = DebugNibble::encrypt(u4(x as u8), &faulty_key).unwrap();
+    #[allow(unused_mut)]
+    let mut output = nibble.apply_sbox(&(lut, shadow), &faulty_context).unwrap();
+
+    // without the output key of the bootstrappings, the fault shows once keyswitched back
+    #[cfg(not(feature = "single_key"))]
+    let operation = Operation::Sbox(opposite.map(|u| u.0));
+    #[cfg(feature = "single_key")]
+    let operation = {
+        output.keyswitch(&faulty_context).unwrap();
+        Operation::Keyswitch
+    };
+    let divergence = faulty_key.divergence().unwrap();
+    assert_eq!(divergence.operation, operation);
+    assert_eq!(divergence.expected.0, opposite[x].0);
+    assert_eq!(divergence.decrypted.0, table[x].0);
+    assert_eq!(output.shadow().0, opposite[x].0);
+}
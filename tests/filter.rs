@@ -0,0 +1,154 @@
+use elisabeth::{
+    u4, xof::XofKind, CustomParameters, ElisabethError, Encrypter, Filter, FilterSpec, Nonce,
+    SystemParameters, Uint,
+};
+
+fn invalid<const BITS: usize>(spec: &FilterSpec<BITS>) -> bool
+where
+    Uint<BITS>: elisabeth::Width,
+{
+    matches!(spec.validate(), Err(ElisabethError::InvalidFilter(_)))
+}
+
+fn encrypt(params: &SystemParameters, key: &[u4], nonce: Nonce, message: &[u4]) -> Vec<u4> {
+    let encrypter = Encrypter::from_symmetric_key(params, key.to_vec()).unwrap();
+    let mut ciphertext = vec![u4(0); message.len()];
+    encrypter.encrypt(nonce, &mut ciphertext, message).unwrap();
+    ciphertext
+}
+
+fn main() {
+    let elisabeth4 = FilterSpec::elisabeth4();
+    elisabeth4.validate().unwrap();
+
+    // S(x + 8) = -S(x) must hold for every S-box
+    let mut spec = elisabeth4.clone();
+    spec.second_layer[2][11] = u4(0x1);
+    assert!(invalid(&spec));
+    let mut spec = elisabeth4.clone();
+    spec.first_layer[0][0] = u4(0x13);
+    assert!(invalid(&spec));
+
+    // every lane needs its S-boxes and wiring, which may only refer to existing lanes
+    let mut spec = elisabeth4.clone();
+    spec.block_width = 6;
+    assert!(invalid(&spec));
+    let mut spec = elisabeth4.clone();
+    spec.block_width = 1;
+    assert!(invalid(&spec));
+    let mut spec = elisabeth4.clone();
+    spec.first_wiring[3] = 4;
+    assert!(invalid(&spec));
+    let mut spec = elisabeth4.clone();
+    spec.second_wiring[1].clear();
+    assert!(invalid(&spec));
+
+    // the key round must split into blocks
+    assert!(matches!(
+        CustomParameters::new(62, 256, elisabeth4.clone(), XofKind::AesCtr),
+        Err(ElisabethError::ParameterMismatch(_))
+    ));
+    assert!(matches!(
+        CustomParameters::new(260, 256, elisabeth4.clone(), XofKind::AesCtr),
+        Err(ElisabethError::ParameterMismatch(_))
+    ));
+    // and the indices of the key must fit in two bytes
+    assert!(matches!(
+        CustomParameters::new(60, (1 << 16) + 1, elisabeth4.clone(), XofKind::AesCtr),
+        Err(ElisabethError::ParameterMismatch(_))
+    ));
+    assert!(CustomParameters::new(60, 1 << 16, elisabeth4.clone(), XofKind::AesCtr).is_ok());
+
+    let key = SystemParameters::n60.generate_symmetric_key();
+    let nonce = Nonce::random();
+    let message = (0..100).map(|i| u4(i % 16)).collect::<Vec<_>>();
+
+    // the description of Elisabeth-4 gives the same cipher as the preset
+    let custom = SystemParameters::Custom(
+        CustomParameters::new(60, 256, elisabeth4.clone(), XofKind::AesCtr).unwrap(),
+    );
+    let expected = encrypt(&SystemParameters::n60, &key, nonce, &message);
+    let ciphertext = encrypt(&custom, &key, nonce, &message);
+    assert!(expected
+        .iter()
+        .zip(ciphertext.iter())
+        .all(|(a, b)| a.0 == b.0));
+
+    // a filter with blocks of 4 nibbles and its own wiring
+    let spec = FilterSpec {
+        block_width: 4,
+        first_layer: elisabeth4.first_layer[..3].to_vec(),
+        second_layer: elisabeth4.second_layer[..3].to_vec(),
+        first_wiring: vec![2, 0, 1],
+        second_wiring: vec![vec![1], vec![0, 2], vec![0, 1]],
+    };
+    let custom =
+        SystemParameters::Custom(CustomParameters::new(48, 256, spec, XofKind::Shake128).unwrap());
+    let ciphertext = encrypt(&custom, &key, nonce, &message);
+
+    let decrypter = Encrypter::from_symmetric_key(&custom, key).unwrap();
+    let mut decryption = vec![u4(0); message.len()];
+    decrypter
+        .decrypt(nonce, &mut decryption, &ciphertext)
+        .unwrap();
+    assert!(message
+        .iter()
+        .zip(decryption.iter())
+        .all(|(a, b)| a.0 == b.0));
+
+    // a filter on nibbles of 3 bits, whose S-boxes satisfy S(x + 4) = -S(x) modulo 8
+    let sboxes = [
+        [1, 6, 3, 0, 7, 2, 5, 0],
+        [5, 2, 4, 7, 3, 6, 4, 1],
+        [0, 3, 1, 6, 0, 5, 7, 2],
+        [7, 4, 2, 1, 1, 4, 6, 7],
+    ]
+    .map(|sbox| sbox.map(Uint::<3>));
+    let spec: FilterSpec<3> = FilterSpec {
+        block_width: 3,
+        first_layer: sboxes[..2].to_vec(),
+        second_layer: sboxes[2..].to_vec(),
+        first_wiring: vec![1, 0],
+        second_wiring: vec![vec![1], vec![0]],
+    };
+    let mut wrong = spec.clone();
+    wrong.first_layer[1][6] = Uint(3);
+    assert!(invalid(&wrong));
+
+    let custom = CustomParameters::new(30, 128, spec.clone(), XofKind::Shake128).unwrap();
+    let key = custom.generate_symmetric_key();
+    assert!(key.iter().all(|u| u.0 < 8));
+    let encrypter = Encrypter::<Uint<3>, 3>::from_symmetric_key(&custom, key.clone()).unwrap();
+
+    // the bitsliced keystream matches the filter evaluated on the whitened key rounds
+    let filter = Filter::new(spec.clone()).unwrap();
+    let prepared = filter.sboxes(&()).unwrap();
+    let (offset, len) = (5, 100);
+    let zeros = vec![Uint(0); len];
+    let mut keystream = vec![Uint(0); len];
+    encrypter
+        .encrypt_at(nonce, offset, &mut keystream, &zeros)
+        .unwrap();
+    for (counter, u) in (offset..).zip(&keystream) {
+        let round = custom.round(nonce, counter).unwrap();
+        let key_round = round
+            .indices()
+            .iter()
+            .zip(round.whitening())
+            .map(|(&i, w)| Uint((key[i].0 + w.0) % 8))
+            .collect::<Vec<_>>();
+        assert_eq!(filter.call(&key_round, &prepared, &()).unwrap().0, u.0);
+    }
+
+    let message = (0..100).map(|i| Uint(i % 8)).collect::<Vec<_>>();
+    let mut ciphertext = vec![Uint(0); message.len()];
+    encrypter.encrypt(nonce, &mut ciphertext, &message).unwrap();
+    let mut decryption = vec![Uint(0); message.len()];
+    encrypter
+        .decrypt(nonce, &mut decryption, &ciphertext)
+        .unwrap();
+    assert!(message
+        .iter()
+        .zip(decryption.iter())
+        .all(|(a, b)| a.0 == b.0));
+}
@@ -1,9 +1,10 @@
 use concrete_core::{crypto::encoding::Plaintext, math::random::RandomGenerator};
 use crossterm::{cursor, QueueableCommand};
-use elisabeth::{u4, Encrypter, SystemParameters, Torus, LWE};
+use elisabeth::{cost::Calibration, u4, Encrypter, Nonce, SystemParameters, Torus, Transcipherer};
 use std::{
     env,
     io::{stdout, Write},
+    thread,
     time::Instant,
 };
 
@@ -20,9 +21,9 @@ fn main() {
     stdout.flush().unwrap();
 
     #[cfg(not(feature = "single_key"))]
-    let ((sk, std_dev_lwe), sk_out, pk) = SystemParameters::n60.generate_fhe_keys();
+    let (lwe_key, sk_out, pk) = SystemParameters::n60.generate_fhe_keys().unwrap();
     #[cfg(feature = "single_key")]
-    let ((sk, std_dev_lwe), pk) = SystemParameters::n60.generate_fhe_keys();
+    let (lwe_key, pk) = SystemParameters::n60.generate_fhe_keys().unwrap();
 
     stdout.queue(cursor::SavePosition).unwrap();
     stdout
@@ -31,12 +32,25 @@ fn main() {
     stdout.queue(cursor::RestorePosition).unwrap();
     stdout.flush().unwrap();
 
-    let (mut encrypter, mut decrypter) = Encrypter::<u4>::new::<LWE>(
+    // client side
+    let encrypter = Encrypter::<u4>::from_symmetric_key(
         &SystemParameters::n60,
-        Some(&sk),
-        Some(std_dev_lwe.0),
-        Some(pk),
-    );
+        SystemParameters::n60.generate_symmetric_key(),
+    )
+    .unwrap();
+    let encrypted_key = encrypter.export_encrypted_key(&lwe_key).unwrap();
+
+    // predicted cost, from the timings of a few operations
+    let calibration = Calibration::measure(&pk, 10).unwrap();
+    let cores = if cfg!(feature = "multithread") {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    } else {
+        1
+    };
+    let estimate = SystemParameters::n60.estimate(&calibration, nb_nibble, cores);
+
+    // server side
+    let transcipherer = Transcipherer::new(&SystemParameters::n60, encrypted_key, pk).unwrap();
 
     stdout.queue(cursor::SavePosition).unwrap();
     stdout
@@ -55,7 +69,6 @@ fn main() {
         .collect::<Vec<u4>>();
 
     let mut ciphertext = vec![u4(0); nb_nibble];
-    let mut transciphered = vec![LWE::allocate(sk.key_size().to_lwe_size()); nb_nibble];
 
     stdout.queue(cursor::SavePosition).unwrap();
     stdout
@@ -64,7 +77,8 @@ fn main() {
     stdout.queue(cursor::RestorePosition).unwrap();
     stdout.flush().unwrap();
 
-    encrypter.encrypt(&mut ciphertext, &message);
+    let nonce = Nonce::random();
+    encrypter.encrypt(nonce, &mut ciphertext, &message).unwrap();
 
     stdout.queue(cursor::SavePosition).unwrap();
     stdout
@@ -74,7 +88,7 @@ fn main() {
     stdout.flush().unwrap();
 
     let now = Instant::now();
-    decrypter.decrypt(&mut transciphered, &ciphertext);
+    let mut transciphered = transcipherer.transcipher(nonce, &ciphertext).unwrap();
     println!(
         "{} nibbles transcrypted in {} s. ({} s/nibble, {} s/b)",
         nb_nibble,
@@ -82,6 +96,12 @@ fn main() {
         now.elapsed().as_secs_f64() / (nb_nibble as f64),
         now.elapsed().as_secs_f64() / (4. * nb_nibble as f64),
     );
+    println!(
+        "Predicted: {} s. and {} MiB on {} cores.",
+        estimate.time.as_secs_f64(),
+        estimate.memory >> 20,
+        cores
+    );
 
     let mut errors = 0;
     let sdk_samples = transciphered
@@ -93,7 +113,7 @@ fn main() {
             #[cfg(not(feature = "single_key"))]
             sk_out.decrypt_lwe(&mut encoded, lwe.as_mut_lwe());
             #[cfg(feature = "single_key")]
-            sk.decrypt_lwe(&mut encoded, lwe.as_mut_lwe());
+            lwe_key.0.decrypt_lwe(&mut encoded, lwe.as_mut_lwe());
 
             let mut decoded = encoded.0 >> 59;
             if decoded & 1 == 1 {
@@ -123,6 +143,12 @@ fn main() {
         "Standard deviation of the noise of the outputs: 2^{}.",
         sdk_std_log2
     );
+    let noise = SystemParameters::n60.noise().unwrap();
+    println!(
+        "Predicted: 2^{}, with a failure probability of 2^{} per nibble.",
+        noise.variance.log2() / 2.,
+        noise.log2_failure
+    );
 
     if errors > 0 {
         panic!(
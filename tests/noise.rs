@@ -0,0 +1,31 @@
+use elisabeth::{noise::NoiseModel, xof::XofKind, CustomParameters, FilterSpec, SystemParameters};
+
+fn main() {
+    // the symmetric key is encrypted with the noise of the input key of the bootstrappings
+    let ((_, std_dev_lwe), ..) = SystemParameters::n60.fhe_parameters();
+    let fresh = NoiseModel::new(&SystemParameters::n60).fresh();
+    assert!((fresh.variance() - std_dev_lwe.0 * std_dev_lwe.0).abs() < 1e-12 * fresh.variance());
+    assert_eq!(fresh.log2_bootstrap_failure(), f64::NEG_INFINITY);
+
+    let report = SystemParameters::n60.noise().unwrap();
+    println!(
+        "Predicted standard deviation of the noise of the outputs: 2^{}, failure probability: 2^{}.",
+        report.variance.log2() / 2.,
+        report.log2_failure
+    );
+    // the outputs come out of bootstrappings, and are far noisier than the key
+    assert!(report.variance > 1000. * fresh.variance());
+    assert!(report.variance < report.max_bootstrap_variance * 2.);
+    assert!(report.log2_failure >= report.log2_bootstrap_failure);
+    assert!(report.log2_failure < -10.);
+
+    // the blocks are independent: half of them give half the noise, and half the bootstrappings
+    let half = SystemParameters::Custom(
+        CustomParameters::new(30, 256, FilterSpec::elisabeth4(), XofKind::AesCtr).unwrap(),
+    )
+    .noise()
+    .unwrap();
+    assert!((report.variance / half.variance - 2.).abs() < 1e-9);
+    assert!((report.log2_bootstrap_failure - half.log2_bootstrap_failure - 1.).abs() < 1e-6);
+    assert_eq!(report.max_bootstrap_variance, half.max_bootstrap_variance);
+}
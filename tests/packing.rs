@@ -0,0 +1,92 @@
+use concrete_core::{
+    crypto::encoding::Plaintext,
+    math::{random::RandomGenerator, tensor::AsRefTensor},
+};
+use elisabeth::{
+    packing, u4, Encrypter, FheSecretKey, Nonce, SystemParameters, Transcipherer, LWE,
+};
+use std::{env, mem, time::Instant};
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let nb_nibble = args[1].parse().unwrap();
+
+    #[cfg(not(feature = "single_key"))]
+    let (lwe_key, sk_out, pk) = SystemParameters::n60.generate_fhe_keys().unwrap();
+    #[cfg(feature = "single_key")]
+    let (lwe_key, pk) = SystemParameters::n60.generate_fhe_keys().unwrap();
+
+    // the transciphered nibbles are under the output key of the bootstrappings, or under their
+    // input key in single key mode
+    #[cfg(not(feature = "single_key"))]
+    let output_key = &sk_out;
+    #[cfg(feature = "single_key")]
+    let output_key = &lwe_key.0;
+    let (packing_secret_key, packing_key) = packing::generate_packing_keys(output_key).unwrap();
+
+    let encrypter = Encrypter::<u4>::from_symmetric_key(
+        &SystemParameters::n60,
+        SystemParameters::n60.generate_symmetric_key(),
+    )
+    .unwrap();
+    let encrypted_key = encrypter.export_encrypted_key(&lwe_key).unwrap();
+    let transcipherer = Transcipherer::new(&SystemParameters::n60, encrypted_key, pk).unwrap();
+
+    let mut generator = RandomGenerator::new(None);
+    let message = generator
+        .random_uniform_n_lsb_tensor::<u8>(nb_nibble, 4)
+        .into_container()
+        .iter()
+        .map(|f| u4(*f))
+        .collect::<Vec<u4>>();
+    let mut ciphertext = vec![u4(0); nb_nibble];
+    let nonce = Nonce::random();
+    encrypter.encrypt(nonce, &mut ciphertext, &message).unwrap();
+    let transciphered = transcipherer.transcipher(nonce, &ciphertext).unwrap();
+
+    let now = Instant::now();
+    let packed = packing_key.pack_all(&transciphered).unwrap();
+    println!(
+        "{} nibbles packed into {} GLWE ciphertexts in {} s.",
+        nb_nibble,
+        packed.len(),
+        now.elapsed().as_secs_f64()
+    );
+    let bytes = |len: usize| len * mem::size_of::<u64>();
+    println!(
+        "{} bytes per transciphered nibble, {} per packed nibble.",
+        bytes(transciphered[0].as_lwe().as_tensor().len()),
+        bytes(packed[0].as_glwe().as_tensor().len()) / packing_key.capacity()
+    );
+
+    // the packed nibbles that no longer decrypt correctly, while their transciphered LWE did
+    let decrypt = |lwe: &LWE, key: &FheSecretKey| {
+        let mut encoded = Plaintext(0);
+        key.decrypt_lwe(&mut encoded, lwe.as_lwe());
+        (encoded.0.wrapping_add(1 << 59) >> 60) as u8 % 16
+    };
+    let decrypted = packed
+        .iter()
+        .flat_map(|p| p.decrypt(&packing_secret_key).unwrap())
+        .collect::<Vec<_>>();
+    let unpacked = packed.iter().flat_map(|p| p.unpack()).collect::<Vec<_>>();
+    assert_eq!(decrypted.len(), nb_nibble);
+    assert_eq!(unpacked.len(), nb_nibble);
+
+    let mut errors = 0;
+    for ((lwe, (d, u)), m) in transciphered
+        .iter()
+        .zip(decrypted.iter().zip(unpacked.iter()))
+        .zip(message.iter())
+    {
+        let before = decrypt(lwe, output_key);
+        assert_eq!(decrypt(u, &packing_secret_key), d.0);
+        if before == m.0 && d.0 != m.0 {
+            errors += 1;
+        }
+    }
+    println!("{} errors introduced by the packing.", errors);
+    // the noise of the packing key, of standard deviation about 2^-13, is far below the 2^-5 that
+    // nibbles of 4 bits tolerate, so that the packing must not change any of them
+    assert_eq!(errors, 0);
+}
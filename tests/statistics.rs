@@ -0,0 +1,81 @@
+use elisabeth::{Nonce, SystemParameters};
+use std::env;
+
+// A test whose statistic exceeds the quantile of order 1 - 10^-4 of its distribution fails.
+const Z_SCORE: f64 = 3.719;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let nb_rounds: u64 = args.get(1).map_or(20_000, |arg| arg.parse().unwrap());
+
+    for (name, params) in [
+        ("AES-CTR", SystemParameters::n60),
+        ("SHAKE128", SystemParameters::n60_shake),
+    ] {
+        println!("{}:", name);
+        check_round_distribution(&params, nb_rounds);
+    }
+}
+
+fn check_round_distribution(params: &SystemParameters, nb_rounds: u64) {
+    let nonce = Nonce::random();
+    let key_size = params.generate_symmetric_key().len();
+    let n = params.round(nonce, 0).unwrap().indices().len();
+
+    let mut index_counts = vec![0_u64; key_size];
+    let mut first_position_counts = vec![0_u64; key_size];
+    let mut last_position_counts = vec![0_u64; key_size];
+    let mut whitening_counts = vec![0_u64; 16];
+
+    for counter in 0..nb_rounds {
+        let round = params.round(nonce, counter).unwrap();
+        let indices = round.indices();
+
+        let mut selected = vec![false; key_size];
+        for &i in indices {
+            assert!(!selected[i], "index {} selected twice in a round", i);
+            selected[i] = true;
+            index_counts[i] += 1;
+        }
+        first_position_counts[indices[0]] += 1;
+        last_position_counts[indices[n - 1]] += 1;
+
+        for w in round.whitening() {
+            whitening_counts[w.0 as usize] += 1;
+        }
+    }
+
+    // the public randomness only depends on the nonce and the counter
+    let round = params.round(nonce, nb_rounds / 2).unwrap();
+    let again = params.round(nonce, nb_rounds / 2).unwrap();
+    assert_eq!(round.indices(), again.indices());
+
+    check_uniformity("key indices over all positions", &index_counts);
+    check_uniformity("key indices at the first position", &first_position_counts);
+    check_uniformity("key indices at the last position", &last_position_counts);
+    check_uniformity("whitening nibbles", &whitening_counts);
+}
+
+/// Runs a chi-squared goodness of fit test of `counts` against the uniform distribution.
+fn check_uniformity(name: &str, counts: &[u64]) {
+    let total: u64 = counts.iter().sum();
+    let expected = total as f64 / counts.len() as f64;
+    let statistic: f64 = counts
+        .iter()
+        .map(|&c| (c as f64 - expected).powi(2) / expected)
+        .sum();
+
+    // Wilson-Hilferty approximation of the quantile of the chi-squared distribution
+    let k = (counts.len() - 1) as f64;
+    let threshold = k * (1. - 2. / (9. * k) + Z_SCORE * (2. / (9. * k)).sqrt()).powi(3);
+
+    println!(
+        "{}: chi-squared = {:.1} ({} degrees of freedom, threshold {:.1})",
+        name, statistic, k, threshold
+    );
+    assert!(
+        statistic < threshold,
+        "{} are not uniformly distributed",
+        name
+    );
+}
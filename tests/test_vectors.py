@@ -0,0 +1,73 @@
+#!/usr/bin/env python3
+"""Generates the known answers of tests/test_vectors.rs.
+
+This is an implementation of the public randomness described in the documentation of the `xof`
+module, written from that description alone, without sharing any code with the crate. It needs
+the `cryptography` package for AES-128.
+
+    python3 tests/test_vectors.py
+"""
+
+import hashlib
+
+from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+
+NONCE = bytes.fromhex("0123456789abcdeffedcba98")
+COUNTER = 42
+N = 60
+KEY_SIZE = 256
+BITS = 4
+
+
+def seed(nonce, counter):
+    """The 12 bytes of the nonce, then the counter as a big-endian 32-bit integer."""
+    return nonce + counter.to_bytes(4, "big")
+
+
+def aes_ctr(nonce, counter):
+    """AES-128 keyed with the seed, encrypting the big-endian blocks 0, 1, 2, ..."""
+    encryptor = Cipher(algorithms.AES(seed(nonce, counter)), modes.ECB()).encryptor()
+    block = 0
+    while True:
+        yield from encryptor.update(block.to_bytes(16, "big"))
+        block += 1
+
+
+def shake128(nonce, counter):
+    """SHAKE128 absorbing the seed. 4096 bytes are far more than a round consumes."""
+    yield from hashlib.shake_128(seed(nonce, counter)).digest(4096)
+
+
+def read(stream, count):
+    return bytes(next(stream) for _ in range(count))
+
+
+def round_randomness(stream):
+    permutation = list(range(KEY_SIZE))
+    for i in range(N):
+        size = KEY_SIZE - i
+        mask = (1 << (size - 1).bit_length()) - 1
+        while True:
+            draw = int.from_bytes(read(stream, 2), "big") & mask
+            if draw < size:
+                break
+        j = i + draw
+        permutation[i], permutation[j] = permutation[j], permutation[i]
+
+    stream_bits = int.from_bytes(read(stream, (N * BITS + 7) // 8), "big")
+    total = (N * BITS + 7) // 8 * 8
+    whitening = [
+        (stream_bits >> (total - (i + 1) * BITS)) & ((1 << BITS) - 1) for i in range(N)
+    ]
+    return permutation[:N], whitening
+
+
+def main():
+    for name, generator in [("AES_CTR", aes_ctr), ("SHAKE", shake128)]:
+        indices, whitening = round_randomness(generator(NONCE, COUNTER))
+        print("{}_INDICES = {}".format(name, indices))
+        print("{}_WHITENING = {}".format(name, whitening))
+
+
+if __name__ == "__main__":
+    main()
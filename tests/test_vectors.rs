@@ -0,0 +1,59 @@
+use elisabeth::{xof::AesCtr, ElisabethError, Nonce, Round, SystemParameters};
+
+// Known answers for the public randomness of the round 42 of the nonce 0x0123456789abcdeffedcba98,
+// computed by tests/test_vectors.py, an independent implementation of the specification of the
+// `xof` module in Python. They check that the crate follows its own specification, not
+// compatibility with the reference implementation of Elisabeth, whose generator differs.
+const NONCE: Nonce = Nonce([
+    0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0xfe, 0xdc, 0xba, 0x98,
+]);
+const COUNTER: u64 = 42;
+
+const AES_CTR_INDICES: [usize; 60] = [
+    44, 123, 230, 120, 42, 3, 150, 58, 140, 138, 252, 91, 88, 226, 237, 208, 106, 234, 94, 86, 69,
+    103, 105, 152, 9, 218, 115, 167, 251, 26, 124, 8, 24, 199, 27, 161, 68, 80, 197, 209, 171, 61,
+    137, 7, 132, 204, 162, 10, 155, 47, 99, 59, 241, 245, 160, 255, 177, 112, 202, 182,
+];
+const AES_CTR_WHITENING: [u8; 60] = [
+    15, 11, 0, 15, 4, 9, 7, 2, 3, 11, 9, 13, 7, 6, 7, 14, 8, 5, 5, 11, 8, 12, 7, 14, 5, 4, 14, 13,
+    6, 8, 11, 3, 11, 7, 4, 15, 7, 13, 7, 14, 8, 3, 4, 4, 0, 8, 15, 11, 0, 11, 2, 12, 4, 6, 13, 13,
+    11, 3, 14, 5,
+];
+
+const SHAKE_INDICES: [usize; 60] = [
+    225, 1, 76, 46, 174, 220, 184, 189, 181, 120, 51, 104, 42, 206, 58, 144, 82, 201, 55, 160, 73,
+    202, 23, 168, 243, 81, 112, 241, 223, 251, 143, 68, 222, 35, 124, 10, 30, 106, 165, 56, 173,
+    80, 20, 127, 84, 200, 11, 77, 64, 94, 105, 87, 176, 146, 39, 242, 126, 115, 15, 60,
+];
+const SHAKE_WHITENING: [u8; 60] = [
+    2, 11, 10, 6, 5, 8, 4, 2, 3, 15, 4, 14, 10, 9, 14, 14, 12, 6, 6, 12, 2, 14, 11, 14, 7, 4, 11,
+    5, 12, 7, 12, 1, 8, 1, 6, 10, 2, 12, 7, 4, 12, 6, 9, 11, 0, 8, 1, 0, 14, 2, 0, 6, 12, 3, 2, 7,
+    3, 2, 0, 0,
+];
+
+fn main() {
+    for (params, indices, whitening) in [
+        (SystemParameters::n60, AES_CTR_INDICES, AES_CTR_WHITENING),
+        (SystemParameters::n60_shake, SHAKE_INDICES, SHAKE_WHITENING),
+    ] {
+        let round = params.round(NONCE, COUNTER).unwrap();
+        assert_eq!(round.indices(), indices);
+        assert_eq!(
+            round.whitening().iter().map(|w| w.0).collect::<Vec<_>>(),
+            whitening
+        );
+        // the counter only has 32 bits in the seed, and must not wrap around
+        assert!(matches!(
+            params.round(NONCE, 1 << 32),
+            Err(ElisabethError::NonceLimit { .. })
+        ));
+    }
+
+    // rounds that can not be drawn are rejected rather than aborting
+    for (n, key_size) in [(300, 256), (60, (1 << 16) + 1)] {
+        assert!(matches!(
+            Round::<4>::generate::<AesCtr>(NONCE, COUNTER, n, key_size),
+            Err(ElisabethError::ParameterMismatch(_))
+        ));
+    }
+}
@@ -0,0 +1,94 @@
+use concrete_core::math::random::RandomGenerator;
+use elisabeth::{
+    trace::{Circuit, Node},
+    u4, ElisabethError, Filter, FilterSpec, SystemParameters,
+};
+
+fn main() {
+    let circuit = SystemParameters::n60.circuit().unwrap();
+    let cost = SystemParameters::n60.cost();
+
+    // the circuit performs what the cost model counts, but for the whitening of the key round
+    // and the final addition of the ciphertext
+    assert_eq!(circuit.inputs, 60);
+    assert_eq!(
+        circuit.count(|node| matches!(node, Node::Sbox { .. })),
+        cost.bootstraps
+    );
+    assert_eq!(
+        circuit.count(|node| matches!(node, Node::Keyswitch { .. })),
+        cost.keyswitches + cost.inverse_keyswitches
+    );
+    assert_eq!(
+        circuit.count(|node| matches!(node, Node::Add { .. })),
+        cost.additions
+    );
+    assert_eq!(circuit.count(|node| matches!(node, Node::AddU4 { .. })), 0);
+
+    // the circuit computes the filter
+    let filter = Filter::new(FilterSpec::elisabeth4()).unwrap();
+    let sboxes = filter.sboxes(&()).unwrap();
+    let mut generator = RandomGenerator::new(None);
+    for _ in 0..100 {
+        let key_round = generator
+            .random_uniform_n_lsb_tensor::<u8>(60, 4)
+            .into_container()
+            .iter()
+            .map(|f| u4(*f))
+            .collect::<Vec<u4>>();
+        assert_eq!(
+            circuit.evaluate(&key_round).unwrap().0,
+            filter.call(&key_round, &sboxes, &()).unwrap().0
+        );
+    }
+
+    // a circuit that does not hold together is rejected instead of being evaluated
+    let key_round = vec![u4(0); 60];
+    let invalid = |circuit: &Circuit| {
+        matches!(
+            circuit.evaluate(&key_round),
+            Err(ElisabethError::InvalidCircuit(_))
+        )
+    };
+    let with_node = |node| {
+        let mut circuit = circuit.clone();
+        circuit.output = circuit.nodes.len();
+        circuit.nodes.push(node);
+        circuit
+    };
+    let last = circuit.nodes.len();
+    assert!(!invalid(&with_node(Node::Keyswitch { input: last - 1 })));
+    assert!(invalid(&with_node(Node::Keyswitch { input: last })));
+    assert!(invalid(&with_node(Node::Add {
+        lhs: 0,
+        rhs: last + 1
+    })));
+    assert!(invalid(&with_node(Node::Input { index: 60 })));
+    assert!(invalid(&with_node(Node::Constant { value: 16 })));
+    assert!(invalid(&with_node(Node::AddU4 {
+        input: 0,
+        value: 255
+    })));
+    assert!(invalid(&with_node(Node::Sbox {
+        input: 0,
+        table: circuit.tables.len()
+    })));
+    let mut wrong = circuit.clone();
+    wrong.output = last;
+    assert!(invalid(&wrong));
+
+    // both exports describe every node
+    let json: serde_json::Value = serde_json::from_str(&circuit.to_json()).unwrap();
+    assert_eq!(json["nodes"].as_array().unwrap().len(), circuit.nodes.len());
+    assert_eq!(json["nodes"][0]["op"], "input");
+    let dot = circuit.to_dot();
+    assert!(dot.starts_with("digraph"));
+    assert_eq!(dot.matches("label=").count(), circuit.nodes.len());
+    assert_eq!(dot.matches("peripheries=2").count(), 1);
+
+    println!(
+        "{} nodes, {} of which are bootstrappings.",
+        circuit.nodes.len(),
+        cost.bootstraps
+    );
+}
@@ -0,0 +1,117 @@
+use concrete_core::math::random::RandomGenerator;
+use elisabeth::{
+    xof::XofKind, CustomParameters, ElisabethError, Encrypter, FheSecretKey, FilterSpec, Nonce,
+    PublicKey, SystemParameters, Transcipherer, Uint, Width,
+};
+use std::{convert::TryFrom, env};
+
+/// Draws a random S-box such that `S(x + 2^(BITS - 1)) = -S(x)`, as the bootstrappings need.
+fn negacyclic_sbox<const BITS: usize>(
+    generator: &mut RandomGenerator,
+) -> <Uint<BITS> as Width>::Table
+where
+    Uint<BITS>: Width,
+{
+    let modulus = Uint::<BITS>::MODULUS;
+    let half = (0..modulus / 2)
+        .map(|_| Uint(generator.random_uniform_n_lsb::<u8>(BITS)))
+        .collect::<Vec<_>>();
+    let table = half
+        .iter()
+        .copied()
+        .chain(half.iter().map(|u| Uint((modulus - u.0) % modulus)))
+        .collect::<Vec<_>>();
+    <Uint<BITS> as Width>::Table::try_from(&table[..])
+        .ok()
+        .unwrap()
+}
+
+/// Returns parameters with a random filter of a single block of 3 nibbles of `BITS` bits.
+fn custom_parameters<const BITS: usize>(generator: &mut RandomGenerator) -> CustomParameters<BITS>
+where
+    Uint<BITS>: Width,
+{
+    let spec = FilterSpec::<BITS> {
+        block_width: 3,
+        first_layer: (0..2).map(|_| negacyclic_sbox(generator)).collect(),
+        second_layer: (0..2).map(|_| negacyclic_sbox(generator)).collect(),
+        first_wiring: vec![1, 0],
+        second_wiring: vec![vec![1], vec![0]],
+    };
+    CustomParameters::new(3, 64, spec, XofKind::AesCtr).unwrap()
+}
+
+/// Encrypts a random message of `nb_nibble` nibbles of `BITS` bits under a random filter,
+/// transciphers it with the FHE keys of `n60`, and returns the number of nibbles that do not
+/// decrypt to the message.
+fn round_trip<const BITS: usize>(
+    lwe_key: &(FheSecretKey, concrete_commons::StandardDev),
+    output_key: &FheSecretKey,
+    pk: &PublicKey,
+    nb_nibble: usize,
+) -> usize
+where
+    Uint<BITS>: Width,
+{
+    let mut generator = RandomGenerator::new(None);
+    let custom = custom_parameters::<BITS>(&mut generator);
+
+    // client side
+    let encrypter =
+        Encrypter::<Uint<BITS>, BITS>::from_symmetric_key(&custom, custom.generate_symmetric_key())
+            .unwrap();
+    let encrypted_key = encrypter.export_encrypted_key(lwe_key).unwrap();
+
+    // server side
+    let transcipherer = Transcipherer::new(&custom, encrypted_key, pk.clone()).unwrap();
+
+    let message = (0..nb_nibble)
+        .map(|_| Uint(generator.random_uniform_n_lsb::<u8>(BITS)))
+        .collect::<Vec<Uint<BITS>>>();
+    let mut ciphertext = vec![Uint(0); nb_nibble];
+    let nonce = Nonce::random();
+    encrypter.encrypt(nonce, &mut ciphertext, &message).unwrap();
+    let transciphered = transcipherer.transcipher(nonce, &ciphertext).unwrap();
+
+    transciphered
+        .iter()
+        .zip(message.iter())
+        .filter(|(lwe, m)| lwe.decrypt(output_key).0 != m.0)
+        .count()
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let nb_nibble = args[1].parse().unwrap();
+
+    #[cfg(not(feature = "single_key"))]
+    let (lwe_key, sk_out, pk) = SystemParameters::n60.generate_fhe_keys().unwrap();
+    #[cfg(feature = "single_key")]
+    let (lwe_key, pk) = SystemParameters::n60.generate_fhe_keys().unwrap();
+
+    // the transciphered nibbles are under the output key of the bootstrappings, or under their
+    // input key in single key mode
+    #[cfg(not(feature = "single_key"))]
+    let output_key = &sk_out;
+    #[cfg(feature = "single_key")]
+    let output_key = &lwe_key.0;
+
+    // a bootstrapping rounds its input to multiples of 1/1024, which adds a noise of standard
+    // deviation about 2^-7.5 with the FHE parameters of `n60`: far below the 2^-4 that nibbles of
+    // 3 bits tolerate, so that none of them may be wrong
+    let errors = round_trip::<3>(&lwe_key, output_key, &pk, nb_nibble);
+    println!("{} errors over {} nibbles of 3 bits.", errors, nb_nibble);
+    assert_eq!(errors, 0);
+
+    // nibbles of 5 bits only tolerate 2^-6, less than 3 standard deviations, so that their key can
+    // not be exported to a server
+    let custom = custom_parameters::<5>(&mut RandomGenerator::new(None));
+    let encrypter =
+        Encrypter::<Uint<5>, 5>::from_symmetric_key(&custom, custom.generate_symmetric_key())
+            .unwrap();
+    assert!(matches!(
+        encrypter.export_encrypted_key(&lwe_key),
+        Err(ElisabethError::ParameterMismatch(_))
+    ));
+    println!("Nibbles of 5 bits are rejected.");
+}